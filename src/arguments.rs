@@ -1,11 +1,22 @@
+use crate::config::UserConfig;
+use crate::data::exclusions::Exclusions;
+use crate::data::filter::FilterOptions;
+use crate::data::filter::Permit;
 use crate::data::trader::ShipSize;
-use crate::data::EdceData;
+use crate::data::journal::default_journal_dir;
+use crate::data::JournalData;
 use crate::search::SearchQuality;
 use crate::user_input::prompt_value;
 use crate::util::num_unit::*;
 use getopts::Matches;
 use std::str::FromStr;
 
+// carrier orders change often, so only markets seen in the last couple of hours are trusted
+const DEFAULT_CARRIER_HOURS: u64 = 2;
+
+// a quarter of smuggling profit is written off to fines and lost cargo, unless configured
+const DEFAULT_SMUGGLING_RISK: f64 = 0.25;
+
 pub struct Arguments {
     pub system: Option<String>,
     pub station: String,
@@ -15,52 +26,78 @@ pub struct Arguments {
     pub jump_range: f64,
     pub ship_size: ShipSize,
     pub search_quality: SearchQuality,
-    pub edce_path: Option<String>,
+    pub journal_path: Option<String>,
     pub auto_accept: bool,
+    pub watch: bool,
+    pub source: Option<String>,
+    pub planetary: bool,
+    pub carrier_hours: Option<u64>,
+    pub permits: Vec<Permit>,
+    pub smuggling: bool,
+    pub smuggling_risk: f64,
+    pub exclusions: Exclusions,
+    pub require_refuel: bool,
+    pub avoid_states: Vec<String>,
+    // commodity names and tons, as given to --hold
+    pub hold: Vec<(String, u32)>,
+    // mission cargo and limpets from the journal's Cargo.json
+    pub reserved_tons: u32,
+    pub eddn: Option<String>,
 }
 
 impl Arguments {
-    pub fn collect(config: &Matches, edce_data: &Option<EdceData>) -> Arguments {
-        if edce_data.is_some() {
-            println!("EDCE data was successfully retrieved.  Some arguments can be automatically extracted.");
+    pub fn collect(
+        config: &Matches,
+        journal_data: &Option<JournalData>,
+        user_config: &UserConfig,
+    ) -> Arguments {
+        if journal_data.is_some() {
+            println!("Player Journal was successfully read.  Some arguments can be automatically extracted.");
         }
 
         let system_in = match config.opt_str("s") {
             Some(t) => Some(t),
-            None => match edce_data {
-                &Some(ref edce) => {
-                    let system = edce.lastSystem.name.clone();
-                    println!("Loaded EDCE data - system location:\t{}", system);
-                    Some(system)
+            None => match journal_data {
+                &Some(JournalData {
+                    system: Some(ref system),
+                    ..
+                }) => {
+                    println!("Loaded Journal data - system location:\t{}", system);
+                    Some(system.clone())
                 }
-                &None => None,
+                _ => None,
             },
         };
 
         let station_in = match config.opt_str("t") {
             Some(t) => t,
-            None => match edce_data {
-                &Some(ref edce) => {
-                    let starport = edce.lastStarport.name.clone();
-                    println!("Loaded EDCE data - starport location:\t{}", starport);
-                    starport
+            None => match journal_data {
+                &Some(JournalData {
+                    station: Some(ref station),
+                    docked: true,
+                    ..
+                }) => {
+                    println!("Loaded Journal data - starport location:\t{}", station);
+                    station.clone()
                 }
-                &None => prompt_value("t", "current station name (e.g. Git Hub)"),
+                _ => prompt_value("t", "current station name (e.g. Git Hub)"),
             },
         };
 
         let balance_in = match config.opt_str("b") {
             Some(v) => v,
-            None => match edce_data {
-                &Some(ref edce) => {
-                    let credits = edce.commander.credits;
+            None => match journal_data {
+                &Some(JournalData {
+                    credits: Some(credits),
+                    ..
+                }) => {
                     println!(
-                        "Loaded EDCE data - credit balance:\t{}",
+                        "Loaded Journal data - credit balance:\t{}",
                         NumericUnit::new_string(credits, &"cr".to_string())
                     );
                     credits.to_string()
                 }
-                &None => prompt_value("b", "current credit balance (e.g. 525.4k or 525412)"),
+                _ => prompt_value("b", "current credit balance (e.g. 525.4k or 525412)"),
             },
         };
         let balance = match NumericUnit::from_str(balance_in.as_ref()) {
@@ -70,13 +107,15 @@ impl Arguments {
 
         let cargo_in = match config.opt_str("c") {
             Some(v) => v,
-            None => match edce_data {
-                &Some(ref edce) => {
-                    let cargo = edce.ship.cargo.capacity.to_string();
-                    println!("Loaded EDCE data - cargo capcity:\t{} tons", cargo);
-                    cargo
+            None => match journal_data {
+                &Some(JournalData {
+                    cargo_capacity: Some(cargo),
+                    ..
+                }) => {
+                    println!("Loaded Journal data - cargo capcity:\t{} tons", cargo);
+                    cargo.to_string()
                 }
-                &None => prompt_value("c", "current cargo capacity in tons (e.g. 216)"),
+                _ => prompt_value("c", "current cargo capacity in tons (e.g. 216)"),
             },
         };
         let cargo_capacity = match NumericUnit::from_str(cargo_in.as_ref()) {
@@ -101,26 +140,43 @@ impl Arguments {
 
         let jump_range_in = match config.opt_str("r") {
             Some(v) => v,
-            None => prompt_value("r", "current laden jump range in light years"),
+            None => match journal_data {
+                &Some(JournalData {
+                    laden_jump_range: Some(range),
+                    ..
+                }) => {
+                    println!("Loaded Journal data - laden jump range:\t{:.2} ly", range);
+                    range.to_string()
+                }
+                _ => prompt_value("r", "current laden jump range in light years"),
+            },
         };
         let jump_range = match NumericUnit::from_str(jump_range_in.as_ref()) {
             Ok(v) => v.to_num(),
             Err(reason) => panic!("Invalid jump range '{}' - {}", jump_range_in, reason),
         };
 
-        let ship_size_in = match config.opt_str("p") {
-            Some(v) => v,
-            None => match Self::get_ship_size(edce_data) {
-                Some(v) => {
-                    println!("Loaded EDCE data - ship size:\t\t{} ", v);
-                    v
-                }
-                None => prompt_value("p", "current ship size [small|med|large], or [s|m|l]"),
-            },
+        let journal_ship_size = match journal_data {
+            &Some(ref journal) => journal.ship_size(),
+            &None => None,
         };
-        let ship_size = match ShipSize::from_str(ship_size_in.as_ref()) {
-            Ok(v) => v,
-            Err(reason) => panic!("Invalid ship size '{}' - {}", ship_size_in, reason),
+
+        let ship_size = match (config.opt_str("p"), journal_ship_size) {
+            (None, Some(v)) => {
+                println!("Loaded Journal data - ship size:\t\t{} ", v);
+                v
+            }
+            (ship_size_in, _) => {
+                let ship_size_in = match ship_size_in {
+                    Some(v) => v,
+                    None => prompt_value("p", "current ship size [small|med|large], or [s|m|l]"),
+                };
+
+                match ShipSize::from_str(ship_size_in.as_ref()) {
+                    Ok(v) => v,
+                    Err(reason) => panic!("Invalid ship size '{}' - {}", ship_size_in, reason),
+                }
+            }
         };
 
         let quality_in = config.opt_str("q").unwrap_or("ultra".to_string());
@@ -138,47 +194,139 @@ impl Arguments {
             minimum_balance: minimum_balance,
             ship_size: ship_size,
             search_quality: quality,
-            edce_path: config.opt_str("C").map(|e| e.replace("\"", "")),
+            journal_path: Self::get_journal_path(config),
             auto_accept: config.opt_present("A"),
+            watch: config.opt_present("w"),
+            source: Self::get_source(config, user_config),
+            planetary: config.opt_present("P") || user_config.planetary,
+            carrier_hours: Self::get_carrier_hours(config, user_config),
+            permits: user_config.permits.clone(),
+            smuggling: config.opt_present("smuggling") || user_config.smuggling,
+            smuggling_risk: Self::get_smuggling_risk(user_config),
+            exclusions: Exclusions::load(),
+            require_refuel: config.opt_present("refuel") || user_config.require_refuel,
+            avoid_states: Self::get_avoid_states(config, user_config),
+            hold: Self::get_hold(config, journal_data),
+            reserved_tons: Self::get_reserved_tons(config, journal_data),
+            eddn: config.opt_str("E").map(|e| e.replace("\"", "")),
         }
     }
 
-    fn get_ship_size(edce_data: &Option<EdceData>) -> Option<String> {
-        match edce_data {
-            &Some(ref edce) => match &edce.ship.name[..] {
-                "Adder" => Some("small"),
-                "Anaconda" => Some("large"),
-                "Asp Explorer" => Some("medium"),
-                "Asp Scout" => Some("medium"),
-                "Cobra Mk III" => Some("small"),
-                "Cobra Mk IV" => Some("small"),
-                "Diamondback Explorer" => Some("small"),
-                "Diamondback Scout" => Some("small"),
-                "Eagle" => Some("small"),
-                "Federal Assault Ship" => Some("medium"),
-                "Federal Corvette" => Some("large"),
-                "Federal Dropship" => Some("medium"),
-                "Federal Gunship" => Some("medium"),
-                "Fer-de-Lance" => Some("medium"),
-                "Hauler" => Some("small"),
-                "Imperial Clipper" => Some("large"),
-                "Imperial Courier" => Some("small"),
-                "Imperial Cutter" => Some("large"),
-                "Imperial Eagle" => Some("small"),
-                "Keelback" => Some("medium"),
-                "Orca" => Some("large"),
-                "Python" => Some("medium"),
-                "Sidewinder" => Some("small"),
-                "Type-6 Transporter" => Some("medium"),
-                "Type-7 Transporter" => Some("large"),
-                "Type-9 Heavy" => Some("large"),
-                "Viper" => Some("small"),
-                "Viper Mk IV" => Some("small"),
-                "Vulture" => Some("small"),
-                _ => None,
+    pub fn filter_options(&self) -> FilterOptions {
+        let mut filter = FilterOptions::new(self.ship_size.clone());
+        filter.planetary = self.planetary;
+        filter.carrier_max_age = self.carrier_hours.map(|hours| hours * 3600);
+        filter.permits = self.permits.clone();
+        filter.smuggling = self.smuggling;
+        filter.exclusions = self.exclusions.clone();
+        filter.require_refuel = self.require_refuel;
+        filter.avoid_states = self.avoid_states.clone();
+        filter
+    }
+
+    // the flag replaces the configured states, rather than adding to them
+    fn get_avoid_states(config: &Matches, user_config: &UserConfig) -> Vec<String> {
+        match config.opt_str("avoid") {
+            Some(states) => states
+                .replace("\"", "")
+                .split(',')
+                .map(|e| e.trim().to_string())
+                .filter(|e| !e.is_empty())
+                .collect(),
+            None => user_config.avoid_states.clone(),
+        }
+    }
+
+    // e.g. "Gold:20,Painite:4.5k"
+    // the flag replaces the journal's Cargo.json - --hold "" plans as if the hold were empty
+    fn get_hold(config: &Matches, journal_data: &Option<JournalData>) -> Vec<(String, u32)> {
+        let hold = match (config.opt_str("hold"), journal_data) {
+            (Some(v), _) => v.replace("\"", ""),
+            (None, &Some(ref journal)) => {
+                let held_cargo = journal.held_cargo();
+                let tons: u32 = held_cargo.iter().map(|e| e.1).sum();
+                if tons > 0 {
+                    println!("Loaded Journal data - cargo aboard:\t{} tons", tons);
+                }
+
+                return held_cargo;
             }
-            .map(|e| e.to_string()),
-            &None => None,
+            (None, &None) => return Vec::new(),
+        };
+
+        hold.split(',')
+            .map(|e| e.trim())
+            .filter(|e| !e.is_empty())
+            .map(|e| {
+                let (name, tons) = match e.rfind(':') {
+                    Some(i) => (e[..i].trim(), e[i + 1..].trim()),
+                    None => panic!("Invalid hold '{}' - expected commodity:tons", e),
+                };
+
+                match NumericUnit::from_str(tons) {
+                    Ok(v) => (name.to_string(), v.to_num::<u32>()),
+                    Err(reason) => panic!("Invalid tons '{}' for {} - {}", tons, name, reason),
+                }
+            })
+            .collect()
+    }
+
+    // like the rest of Cargo.json, replaced by --hold
+    fn get_reserved_tons(config: &Matches, journal_data: &Option<JournalData>) -> u32 {
+        match (config.opt_present("hold"), journal_data) {
+            (false, &Some(ref journal)) => {
+                let tons = journal.reserved_tons();
+                if tons > 0 {
+                    println!("Loaded Journal data - mission cargo and limpets:\t{} tons", tons);
+                }
+
+                tons
+            }
+            _ => 0,
+        }
+    }
+
+    fn get_smuggling_risk(user_config: &UserConfig) -> f64 {
+        match user_config.smuggling_risk {
+            Some(risk) if risk >= 0f64 && risk <= 1f64 => risk,
+            Some(risk) => panic!(
+                "Invalid smuggling_risk {} in config.json - expected a fraction between 0.0 and 1.0",
+                risk
+            ),
+            None => DEFAULT_SMUGGLING_RISK,
+        }
+    }
+
+    pub fn get_carrier_hours(config: &Matches, user_config: &UserConfig) -> Option<u64> {
+        if !config.opt_present("C") {
+            return user_config.carriers;
+        }
+
+        match config.opt_str("C") {
+            Some(hours) => match u64::from_str(&hours[..]) {
+                Ok(v) => Some(v),
+                Err(reason) => panic!("Invalid carrier freshness '{}' - {}", hours, reason),
+            },
+            None => Some(DEFAULT_CARRIER_HOURS),
+        }
+    }
+
+    pub fn get_source(config: &Matches, user_config: &UserConfig) -> Option<String> {
+        match config.opt_str("S") {
+            Some(path) => Some(path.replace("\"", "")),
+            None => user_config.source.clone(),
+        }
+    }
+
+    pub fn get_journal_path(config: &Matches) -> Option<String> {
+        // watch mode tails the journal, so it implies journal integration
+        if !config.opt_present("J") && !config.opt_present("w") {
+            return None;
+        }
+
+        match config.opt_str("J") {
+            Some(path) => Some(path.replace("\"", "")),
+            None => default_journal_dir().to_str().map(|e| e.to_string()),
         }
     }
 }
@@ -0,0 +1,54 @@
+use serde::Deserialize;
+use serde::Serialize;
+
+use std::path::PathBuf;
+
+use crate::data::filter::Permit;
+use crate::persist::*;
+
+// user settings which rarely change, stored in ~/.elite_trader/config.json
+// command line flags take precedence over these values.
+//
+// example:
+// {
+//   "source": "C:\\Users\\me\\Downloads\\galaxy_populated.json",
+//   "permits": ["Sol", "Achenar", 9462],
+//   "avoid_states": ["Lockdown", "Civil War"]
+// }
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(default)]
+pub struct UserConfig {
+    // a directory with eddb-style dumps, or a spansh galaxy_populated.json file
+    pub source: Option<String>,
+    // include planetary ports and settlements in routes
+    pub planetary: bool,
+    // include fleet carriers whose market was updated within this many hours
+    pub carriers: Option<u64>,
+    // permit-locked systems the commander can enter, by name or eddb system id
+    pub permits: Vec<Permit>,
+    // sell illegal goods on black markets
+    pub smuggling: bool,
+    // the fraction of smuggling profit expected to be lost to fines and seized cargo (0.0 - 1.0)
+    pub smuggling_risk: Option<f64>,
+    // only route through stations which sell fuel
+    pub require_refuel: bool,
+    // faction states to stay away from, e.g. Lockdown or War
+    pub avoid_states: Vec<String>,
+}
+
+impl UserConfig {
+    pub fn get_path() -> PathBuf {
+        get_base_directory().join("config.json")
+    }
+
+    pub fn load() -> UserConfig {
+        let path = Self::get_path();
+
+        if path.exists() {
+            read_json(&path)
+        } else {
+            UserConfig::default()
+        }
+    }
+}
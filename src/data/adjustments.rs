@@ -1,119 +1,368 @@
 extern crate time;
 
-use serde::de::DeserializeOwned;
+use serde::Deserialize;
 use serde::Serialize;
-use std::fs::create_dir_all;
 use std::fs::read_dir;
+use std::fs::remove_dir;
+use std::fs::remove_file;
+use std::fs::rename;
+use std::fs::File;
+use std::fs::OpenOptions;
+use std::io::BufRead;
+use std::io::BufReader;
+use std::io::Read;
+use std::io::Seek;
+use std::io::SeekFrom;
+use std::io::Write;
 use std::path::Path;
 use std::path::PathBuf;
 
+use crate::data::price_adjustment::PriceAdjustment;
+use crate::data::time_adjustment::TimeAdjustment;
 use crate::persist::*;
-use time::PrimitiveDateTime;
-
-// there is a big problem with the design of the adjustment system.
-// the adjustments are applied as patches to the Universe
-// this is nice to apply, but has two performance downsides
-// 1) applying a patch requires enumerating the entire Universe system array - O(n^2) performance
-// 2) since the Station struct holds the station time adjustment data,
-//		we need to reindex the entire universe after each adjustment
-//		we cannot reindex individual systems because the Octree cannot update or replace items
-
-fn get_adjustments_dir() -> PathBuf {
-    let basedir = get_base_directory();
-    let adjustment_path = basedir.join(Path::new("adjustments"));
-
-    match create_dir_all(&adjustment_path) {
-        Ok(_) => {}
-        Err(reason) => panic!(
-            "Failed to create adjustments dir {}: {}",
-            adjustment_path.to_str().unwrap_or("<unknown>"),
+use crate::util::map_list::MapList;
+
+// adjustments are corrections the player made to the universe - prices they saw, and times they flew.
+// they are stored in a single append-only log, one JSON record per line, in the order they were made.
+//
+// appends write the whole line at once, and sync before returning.
+// if we crash mid-write, the torn line at the end is skipped when the log is read,
+// and the next append starts on a fresh line.
+//
+// the log is indexed by station when it's loaded, so patching the universe is one station lookup per station,
+// instead of a scan of the universe for every adjustment.
+// housekeeping compacts the log by rewriting it without the records which no longer apply.
+
+const LOG_FILENAME: &'static str = "adjustments.log";
+
+// older versions wrote one JSON file per adjustment into this directory
+const LEGACY_DIR: &'static str = "adjustments";
+
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum AdjustmentRecord {
+    Price(PriceAdjustment),
+    Time(TimeAdjustment),
+}
+
+pub fn get_log_path() -> PathBuf {
+    get_base_directory().join(LOG_FILENAME)
+}
+
+pub fn save_adjustment(record: AdjustmentRecord) {
+    let path = get_log_path();
+
+    if let Err(reason) = append_record(&path, &record) {
+        panic!(
+            "Failed to append adjustment to {}: {}",
+            path.to_str().unwrap_or("<unknown>"),
             reason
-        ),
+        );
     }
+}
+
+fn append_record(path: &Path, record: &AdjustmentRecord) -> std::io::Result<()> {
+    let mut line = match serde_json::to_string(record) {
+        Ok(l) => l,
+        Err(reason) => panic!("Failed to encode adjustment: {}", reason),
+    };
+    line.push('\n');
 
-    adjustment_path
+    let mut file = OpenOptions::new()
+        .read(true)
+        .append(true)
+        .create(true)
+        .open(path)?;
+
+    // terminate a line torn by an earlier crash, so this record isn't glued onto it
+    let len = file.metadata()?.len();
+    if len > 0 {
+        let mut last = [0u8; 1];
+        file.seek(SeekFrom::Start(len - 1))?;
+        file.read_exact(&mut last)?;
+        if last[0] != b'\n' {
+            line.insert(0, '\n');
+        }
+    }
+
+    file.write_all(line.as_bytes())?;
+    file.sync_data()
 }
 
-// todo: make category an enum?
-// this would allow callers to save/load adjustments using the generic method
-// or maybe we could create a trait.. hmm...
-pub fn save_adjustment<A>(category: &str, adjustment: &A)
-where
-    A: Serialize,
-{
-    let timespec = PrimitiveDateTime::now();
-    let time_str = format!("{:010}-{:09}", timespec.timestamp(), timespec.nanosecond());
+// replaces the log with the given records.  used by housekeeping to compact the log
+pub fn rewrite_adjustments(records: &Vec<AdjustmentRecord>) {
+    let path = get_log_path();
+    let tmp_path = path.with_extension("log.tmp");
+
+    let result = File::create(&tmp_path).and_then(|mut file| {
+        for record in records {
+            let line = match serde_json::to_string(record) {
+                Ok(l) => l,
+                Err(reason) => panic!("Failed to encode adjustment: {}", reason),
+            };
 
-    let filename = format!("adjustment_{}.{}.json", category, time_str);
-    let filepath = get_adjustments_dir().join(filename);
+            file.write_all(line.as_bytes())?;
+            file.write_all(b"\n")?;
+        }
+
+        file.sync_all()
+    });
 
-    write_json(&filepath, adjustment);
+    if let Err(reason) = result.and_then(|_| rename(&tmp_path, &path)) {
+        panic!(
+            "Failed to rewrite adjustment log {}: {}",
+            path.to_str().unwrap_or("<unknown>"),
+            reason
+        );
+    }
 }
 
-struct LoadedAdjustment<T> {
-    filename: String,
-    adjustment: T,
+pub struct AdjustmentLog {
+    pub records: Vec<AdjustmentRecord>,
+    // lines which could not be read, usually a write torn by a crash
+    pub skipped: usize,
+    prices_by_station: MapList<u32, usize>,
 }
 
-pub fn load_adjustments<A>(category: &str) -> Vec<A>
-where
-    A: DeserializeOwned,
-{
-    let mut vec = Vec::new();
+impl AdjustmentLog {
+    pub fn load() -> AdjustmentLog {
+        let path = get_log_path();
+        migrate_legacy_files(&get_base_directory().join(LEGACY_DIR), &path);
 
-    let prefix_string = "adjustment_".to_string() + category + ".";
-    let prefix = &prefix_string[..];
+        let log = AdjustmentLog::read(&path);
+        if log.skipped > 0 {
+            println!(
+                "Skipped {} unreadable records in {}",
+                log.skipped,
+                path.to_str().unwrap_or("<unknown>")
+            );
+        }
 
-    match read_dir(get_adjustments_dir()) {
-        Ok(results) => {
-            for entry in results {
-                let entry = match entry {
-                    Ok(o) => o,
-                    Err(_) => {
-                        continue;
-                    }
-                };
+        log
+    }
+
+    fn read(path: &Path) -> AdjustmentLog {
+        let mut records = Vec::new();
+        let mut skipped = 0;
 
-                let path = entry.path();
-                let filename = match path.file_name() {
-                    Some(f) => match f.to_str() {
-                        Some(f2) => f2.to_string(),
-                        None => {
-                            continue;
-                        }
-                    },
-                    None => {
+        if let Ok(file) = File::open(path) {
+            for line in BufReader::new(file).lines() {
+                let line = match line {
+                    Ok(l) => l,
+                    Err(_) => {
+                        skipped += 1;
                         continue;
                     }
                 };
 
-                if !filename.starts_with(prefix) {
+                if line.trim().is_empty() {
                     continue;
                 }
 
-                let val: A = read_json(path.as_path());
-                vec.push(LoadedAdjustment {
-                    filename: filename,
-                    adjustment: val,
-                });
+                match serde_json::from_str(&line) {
+                    Ok(record) => records.push(record),
+                    Err(_) => skipped += 1,
+                }
             }
         }
-        Err(reason) => panic!(
-            "Failed to read adjustments dir {}: {}",
-            get_adjustments_dir()
-                .as_path()
-                .to_str()
-                .unwrap_or("<unknown>"),
-            reason
-        ),
+
+        AdjustmentLog::new(records, skipped)
+    }
+
+    fn new(records: Vec<AdjustmentRecord>, skipped: usize) -> AdjustmentLog {
+        let mut prices_by_station = MapList::new();
+        for (i, record) in records.iter().enumerate() {
+            if let &AdjustmentRecord::Price(ref price) = record {
+                prices_by_station.insert(price.station_id, i);
+            }
+        }
+
+        AdjustmentLog {
+            records: records,
+            skipped: skipped,
+            prices_by_station: prices_by_station,
+        }
+    }
+
+    // the stations which have price adjustments
+    pub fn price_stations(&self) -> Vec<u32> {
+        self.prices_by_station.keys().cloned().collect()
+    }
+
+    // price adjustments for the station, oldest first
+    pub fn prices_for_station(&self, station_id: u32) -> Vec<&PriceAdjustment> {
+        match self.prices_by_station.get(&station_id) {
+            Some(indices) => indices
+                .iter()
+                .filter_map(|&i| match self.records[i] {
+                    AdjustmentRecord::Price(ref price) => Some(price),
+                    _ => None,
+                })
+                .collect(),
+            None => Vec::new(),
+        }
+    }
+
+    // time adjustments, oldest first
+    pub fn times(&self) -> Vec<&TimeAdjustment> {
+        self.records
+            .iter()
+            .filter_map(|e| match e {
+                &AdjustmentRecord::Time(ref time) => Some(time),
+                _ => None,
+            })
+            .collect()
+    }
+}
+
+// moves the one-file-per-adjustment directory into the log, in the order the files were written
+fn migrate_legacy_files(dir: &Path, log_path: &Path) {
+    let entries = match read_dir(dir) {
+        Ok(e) => e,
+        Err(_) => {
+            return;
+        }
+    };
+
+    let mut files: Vec<(String, PathBuf)> = entries
+        .filter_map(|e| e.ok())
+        .map(|e| e.path())
+        .filter_map(|p| {
+            let filename = p.file_name()?.to_str()?.to_string();
+            match filename.starts_with("adjustment_") && filename.ends_with(".json") {
+                true => Some((filename, p)),
+                false => None,
+            }
+        })
+        .collect();
+
+    if files.is_empty() {
+        remove_dir(dir).ok();
+        return;
     }
 
-    vec.sort_by(|a, b| {
-        a.filename
-            .partial_cmp(&b.filename)
-            .expect("Failed to compare adjustment filename order")
+    // filenames are adjustment_<category>.<timestamp>, so sort by the timestamp part
+    files.sort_by(|a, b| {
+        let a_time = a.0.splitn(2, '.').nth(1).unwrap_or("");
+        let b_time = b.0.splitn(2, '.').nth(1).unwrap_or("");
+        a_time.cmp(b_time)
     });
-    // workaround for a lifetime problem
-    let newvec = vec.drain(..).map(|e| e.adjustment).collect();
-    newvec
+
+    println!(
+        "Migrating {} adjustment files into {} ...",
+        files.len(),
+        log_path.to_str().unwrap_or("<unknown>")
+    );
+
+    for (filename, path) in files {
+        let text = match File::open(&path).map(|mut f| {
+            let mut s = String::new();
+            f.read_to_string(&mut s).map(|_| s)
+        }) {
+            Ok(Ok(s)) => s,
+            _ => {
+                continue;
+            }
+        };
+
+        let record = match filename.starts_with("adjustment_price.") {
+            true => serde_json::from_str(&text).map(AdjustmentRecord::Price),
+            false => serde_json::from_str(&text).map(AdjustmentRecord::Time),
+        };
+
+        match record {
+            Ok(record) => {
+                if let Err(reason) = append_record(log_path, &record) {
+                    panic!("Failed to migrate adjustment {}: {}", filename, reason);
+                }
+            }
+            Err(reason) => println!("Skipping unreadable adjustment {}: {}", filename, reason),
+        }
+
+        remove_file(&path).ok();
+    }
+
+    remove_dir(dir).ok();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::util::test_dir::test_dir;
+    use std::fs::create_dir_all;
+    use std::fs::remove_dir_all;
+
+    fn price(station_id: u32, sell_price: u32, timestamp: u64) -> PriceAdjustment {
+        PriceAdjustment {
+            buy_price: None,
+            sell_price: Some(sell_price),
+            supply: None,
+            system_id: 1,
+            station_id: station_id,
+            commodity_id: 1,
+            timestamp: timestamp,
+        }
+    }
+
+    #[test]
+    fn test_log_skips_torn_tail() {
+        let dir = test_dir("adjustment_log");
+        let path = dir.join(LOG_FILENAME);
+
+        append_record(&path, &AdjustmentRecord::Price(price(20, 100, 1))).unwrap();
+        append_record(&path, &AdjustmentRecord::Price(price(10, 200, 2))).unwrap();
+
+        // a crash in the middle of a write
+        let mut file = OpenOptions::new().append(true).open(&path).unwrap();
+        file.write_all(b"{\"type\":\"price\",\"buy_pr").unwrap();
+        drop(file);
+
+        append_record(&path, &AdjustmentRecord::Price(price(20, 300, 3))).unwrap();
+
+        let log = AdjustmentLog::read(&path);
+        assert_eq!(3, log.records.len());
+        assert_eq!(1, log.skipped);
+
+        let station_20: Vec<u32> = log
+            .prices_for_station(20)
+            .iter()
+            .map(|e| e.sell_price.unwrap())
+            .collect();
+        assert_eq!(vec![100, 300], station_20);
+        assert_eq!(1, log.prices_for_station(10).len());
+        assert!(log.prices_for_station(30).is_empty());
+
+        remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn test_migrate_legacy_files() {
+        let dir = test_dir("adjustment_migrate");
+        let legacy = dir.join(LEGACY_DIR);
+        create_dir_all(&legacy).unwrap();
+        let path = dir.join(LOG_FILENAME);
+
+        // written out of order, so the sort matters
+        write_json(
+            &legacy.join("adjustment_price.1592136001-000000000.json"),
+            &price(20, 200, 2),
+        );
+        write_json(
+            &legacy.join("adjustment_price.1592136000-000000000.json"),
+            &price(20, 100, 1),
+        );
+
+        migrate_legacy_files(&legacy, &path);
+        assert!(!legacy.exists());
+
+        let log = AdjustmentLog::read(&path);
+        let prices: Vec<u64> = log
+            .prices_for_station(20)
+            .iter()
+            .map(|e| e.timestamp)
+            .collect();
+        assert_eq!(vec![1, 2], prices);
+
+        remove_dir_all(&dir).ok();
+    }
 }
@@ -0,0 +1,290 @@
+use std::fs::read_dir;
+use std::fs::rename;
+use std::fs::File;
+use std::io::BufReader;
+use std::io::Write;
+use std::path::Path;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use filetime::set_file_mtime;
+use filetime::FileTime;
+use memmap2::Mmap;
+
+use crate::data::trader::System;
+
+// the universe cache is a small header, followed by the bincode-encoded systems.
+//
+// header layout (little endian):
+//  0..8    magic bytes
+//  8..12   schema version
+//  12..20  payload length in bytes
+//
+// bump CACHE_SCHEMA_VERSION whenever System, Station, Listing or Commodity change shape,
+// or when the import keeps stations it used to drop.
+// bincode is positional, so an old cache can't be read with new structs - it is rebuilt from the source instead.
+// systems and stations are shared between universe snapshots with Arc.
+// serde writes an Arc as the plain value, so sharing doesn't change the layout
+
+const CACHE_MAGIC: &'static [u8; 8] = b"ELTRUNIV";
+// v2: planetary stations are kept in the cache
+// v3: fleet carriers are kept in the cache
+// v4: jurisdictions, black markets and average prices
+// v5: stale markets are kept in the cache
+// v6: station services, economies and states, system security and states
+// v7: stable spansh commodity ids, and the built-in permit list
+// v8: unknown refuel service
+pub const CACHE_SCHEMA_VERSION: u32 = 8;
+const HEADER_LEN: usize = 20;
+
+// caches written by older versions were JSON, with the crate version in the name
+const LEGACY_PREFIX: &'static str = "elite_universe_";
+const LEGACY_SUFFIX: &'static str = ".min.json";
+
+pub enum CacheRead {
+    Current(Vec<Arc<System>>),
+    // written with a different schema version.  needs a rebuild
+    Outdated(u32),
+    Invalid(String),
+}
+
+pub fn read_systems(path: &Path) -> CacheRead {
+    let file = match File::open(path) {
+        Ok(f) => f,
+        Err(reason) => {
+            return CacheRead::Invalid(format!("failed to open: {}", reason));
+        }
+    };
+
+    // safe as long as the file isn't modified while mapped.
+    // write_systems replaces the file with a rename, so existing mappings keep the old inode
+    let mmap = match unsafe { Mmap::map(&file) } {
+        Ok(m) => m,
+        Err(reason) => {
+            return CacheRead::Invalid(format!("failed to map: {}", reason));
+        }
+    };
+
+    if mmap.len() < HEADER_LEN || &mmap[0..8] != &CACHE_MAGIC[..] {
+        return CacheRead::Invalid("missing cache header".to_string());
+    }
+
+    let version = read_u32(&mmap[8..12]);
+    if version != CACHE_SCHEMA_VERSION {
+        return CacheRead::Outdated(version);
+    }
+
+    let len = read_u64(&mmap[12..20]) as usize;
+    if mmap.len() < HEADER_LEN + len {
+        return CacheRead::Invalid("truncated payload".to_string());
+    }
+
+    match bincode::deserialize(&mmap[HEADER_LEN..HEADER_LEN + len]) {
+        Ok(systems) => CacheRead::Current(systems),
+        Err(reason) => CacheRead::Invalid(format!("failed to decode: {}", reason)),
+    }
+}
+
+pub fn write_systems(path: &Path, systems: &Vec<Arc<System>>) {
+    let payload = match bincode::serialize(systems) {
+        Ok(p) => p,
+        Err(reason) => panic!("Failed to encode universe cache: {}", reason),
+    };
+
+    let mut header = Vec::with_capacity(HEADER_LEN);
+    header.extend_from_slice(&CACHE_MAGIC[..]);
+    header.extend_from_slice(&CACHE_SCHEMA_VERSION.to_le_bytes());
+    header.extend_from_slice(&(payload.len() as u64).to_le_bytes());
+
+    // write to a temporary file, and swap it in, so a crash never leaves a half-written cache
+    let tmp_path = path.with_extension("tmp");
+    let result = File::create(&tmp_path).and_then(|mut file| {
+        file.write_all(&header)?;
+        file.write_all(&payload)?;
+        file.sync_all()
+    });
+
+    if let Err(reason) = result {
+        panic!(
+            "Failed to write file {}, reason: {}",
+            tmp_path.to_str().unwrap_or("<unknown>"),
+            reason
+        );
+    }
+
+    if let Err(reason) = rename(&tmp_path, path) {
+        panic!(
+            "Failed to replace file {}, reason: {}",
+            path.to_str().unwrap_or("<unknown>"),
+            reason
+        );
+    }
+}
+
+// converts the newest JSON cache from an older version, if there is one.
+// the modification time is carried over, so a stale cache still refreshes on schedule
+pub fn migrate_legacy(path: &Path) -> bool {
+    let dir = match path.parent() {
+        Some(d) => d,
+        None => {
+            return false;
+        }
+    };
+
+    let legacy = match newest_legacy_cache(dir) {
+        Some(p) => p,
+        None => {
+            return false;
+        }
+    };
+
+    let systems: Vec<Arc<System>> = match File::open(&legacy)
+        .map_err(|e| e.to_string())
+        .and_then(|f| serde_json::from_reader(BufReader::new(f)).map_err(|e| e.to_string()))
+    {
+        Ok(s) => s,
+        Err(reason) => {
+            println!(
+                "Could not migrate legacy cache {}: {}",
+                legacy.to_str().unwrap_or("<unknown>"),
+                reason
+            );
+            return false;
+        }
+    };
+
+    println!(
+        "Migrating legacy cache {} ...",
+        legacy.to_str().unwrap_or("<unknown>")
+    );
+    write_systems(path, &systems);
+
+    if let Ok(meta) = legacy.metadata() {
+        let modtime = FileTime::from_last_modification_time(&meta);
+        set_file_mtime(path, modtime).ok();
+    }
+
+    true
+}
+
+pub fn legacy_caches(dir: &Path) -> Vec<PathBuf> {
+    let entries = match read_dir(dir) {
+        Ok(e) => e,
+        Err(_) => {
+            return Vec::new();
+        }
+    };
+
+    entries
+        .filter_map(|e| e.ok())
+        .map(|e| e.path())
+        .filter(|p| {
+            p.file_name()
+                .and_then(|n| n.to_str())
+                .map(|n| n.starts_with(LEGACY_PREFIX) && n.ends_with(LEGACY_SUFFIX))
+                .unwrap_or(false)
+        })
+        .collect()
+}
+
+fn newest_legacy_cache(dir: &Path) -> Option<PathBuf> {
+    legacy_caches(dir)
+        .into_iter()
+        .filter_map(|p| {
+            let modtime = p
+                .metadata()
+                .ok()
+                .map(|m| FileTime::from_last_modification_time(&m))?;
+            Some((modtime, p))
+        })
+        .max_by(|a, b| a.0.cmp(&b.0))
+        .map(|(_, p)| p)
+}
+
+fn read_u32(bytes: &[u8]) -> u32 {
+    let mut buf = [0u8; 4];
+    buf.copy_from_slice(bytes);
+    u32::from_le_bytes(buf)
+}
+
+fn read_u64(bytes: &[u8]) -> u64 {
+    let mut buf = [0u8; 8];
+    buf.copy_from_slice(bytes);
+    u64::from_le_bytes(buf)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::source::FixtureSource;
+    use crate::data::filter::FilterOptions;
+    use crate::data::trader::ShipSize;
+    use crate::data::Universe;
+    use crate::util::test_dir::test_dir;
+    use std::fs::remove_dir_all;
+
+    #[test]
+    fn test_cache_roundtrip() {
+        let dir = test_dir("cache_roundtrip");
+        let path = dir.join("universe.cache");
+        let universe = Universe::from_source(
+            &FixtureSource::sample(),
+            &FilterOptions::new(ShipSize::Small),
+        );
+
+        write_systems(&path, &universe.systems);
+
+        match read_systems(&path) {
+            CacheRead::Current(systems) => {
+                assert_eq!(universe.systems.len(), systems.len());
+                assert_eq!(
+                    universe.systems[1].stations[0].station_name,
+                    systems[1].stations[0].station_name
+                );
+                assert_eq!(
+                    universe.systems[1].stations[0].market_id,
+                    systems[1].stations[0].market_id
+                );
+            }
+            _ => panic!("cache should be readable"),
+        }
+
+        // an older schema is reported, so the caller can rebuild
+        let mut bytes = std::fs::read(&path).unwrap();
+        bytes[8..12].copy_from_slice(&0u32.to_le_bytes());
+        std::fs::write(&path, &bytes).unwrap();
+        match read_systems(&path) {
+            CacheRead::Outdated(0) => {}
+            _ => panic!("cache should be outdated"),
+        }
+
+        std::fs::write(&path, b"[]").unwrap();
+        match read_systems(&path) {
+            CacheRead::Invalid(_) => {}
+            _ => panic!("a json file is not a cache"),
+        }
+
+        remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn test_migrate_legacy() {
+        let dir = test_dir("cache_migrate");
+        let path = dir.join("universe.cache");
+        let universe = Universe::from_source(
+            &FixtureSource::sample(),
+            &FilterOptions::new(ShipSize::Large),
+        );
+
+        let legacy = dir.join("elite_universe_0.9.0.min.json");
+        std::fs::write(&legacy, serde_json::to_string(&universe.systems).unwrap()).unwrap();
+
+        assert!(migrate_legacy(&path));
+        match read_systems(&path) {
+            CacheRead::Current(systems) => assert_eq!(universe.systems.len(), systems.len()),
+            _ => panic!("migrated cache should be readable"),
+        }
+
+        remove_dir_all(&dir).ok();
+    }
+}
@@ -11,13 +11,28 @@ pub struct SystemJson {
     pub y: f64,
     pub z: f64,
     pub needs_permit: Option<bool>,
-    pub updated_at: u64, //	faction: Option<String>,
-                         //	population: Option<u64>,
-                         //	government: Option<String>,
-                         //	allegiance: Option<String>,
-                         //	state: Option<String>,
-                         //	security: Option<String>,
-                         //	primary_economy: Option<String>
+    pub updated_at: u64,
+    // the controlling faction's allegiance and government set the law in the system
+    #[serde(default)]
+    pub controlling_minor_faction: Option<String>,
+    #[serde(default)]
+    pub government: Option<String>,
+    #[serde(default)]
+    pub allegiance: Option<String>,
+    #[serde(default)]
+    pub population: Option<u64>,
+    #[serde(default)]
+    pub states: Vec<StateJson>,
+    #[serde(default)]
+    pub security: Option<String>,
+    #[serde(default)]
+    pub primary_economy: Option<String>,
+}
+
+// eddb states are objects, e.g. { "id": 16, "name": "Boom" }
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct StateJson {
+    pub name: String,
 }
 
 impl Identified<u32> for SystemJson {
@@ -34,21 +49,37 @@ pub struct StationJson {
     pub max_landing_pad_size: Option<String>,
     pub distance_to_star: Option<u32>,
     pub prohibited_commodities: Vec<String>,
-    //	faction: Option<String>,
+    // eddb only has the faction id.  spansh has the name
+    #[serde(default)]
+    pub controlling_minor_faction: Option<String>,
     pub government: Option<String>,
-    //	allegiance: Option<String>,
-    //	state: Option<String>,
-    //	has_blackmarket: Option<u8>,
+    #[serde(default)]
+    pub allegiance: Option<String>,
+    #[serde(default)]
+    pub states: Vec<StateJson>,
+    #[serde(default)]
+    pub has_blackmarket: Option<bool>,
     //	has_market: Option<u8>,
-    //	has_refuel: Option<u8>,
-    //	has_rearm: Option<u8>,
-    //	has_shipyard: Option<u8>,
+    #[serde(default)]
+    pub has_refuel: Option<bool>,
+    #[serde(default)]
+    pub has_repair: Option<bool>,
+    #[serde(default)]
+    pub has_rearm: Option<bool>,
+    #[serde(default)]
+    pub has_outfitting: Option<bool>,
+    #[serde(default)]
+    pub has_shipyard: Option<bool>,
     //	import_commodities: Box<Vec<String>>,
     //	export_commodities: Box<Vec<String>>,
-    //	economies: Box<Vec<String>>,
+    #[serde(default)]
+    pub economies: Vec<String>,
     pub updated_at: u64,
     pub market_updated_at: Option<u64>,
     pub is_planetary: Option<bool>,
+    // the game's market id, which EDDN and the journal use to identify the station
+    #[serde(rename = "ed_market_id", default)]
+    pub market_id: Option<u64>,
 }
 
 impl Identified<u32> for StationJson {
@@ -74,7 +105,8 @@ pub struct CommodityJson {
     pub id: u16,
     pub name: String,
     pub category_id: u8,
-    //	pub average_price: Option<u32>,
+    #[serde(default)]
+    pub average_price: Option<u32>,
     pub category: CommodityCategoryJson,
 }
 
@@ -90,6 +122,14 @@ pub struct CommodityCategoryJson {
     pub name: String,
 }
 
+// the complete set of records needed to build the universe
+pub struct EddbDump {
+    pub commodities: Vec<CommodityJson>,
+    pub systems: Vec<SystemJson>,
+    pub stations: Vec<StationJson>,
+    pub listings: Vec<StationCommodityListingJson>,
+}
+
 pub fn get_stations_by_system(stations: &Vec<StationJson>) -> HashMap<u32, Vec<&StationJson>> {
     let mut result = HashMap::new();
 
@@ -0,0 +1,247 @@
+use std::fs::File;
+use std::io;
+use std::io::BufRead;
+use std::io::BufReader;
+use std::sync::mpsc::channel;
+use std::sync::mpsc::Receiver;
+use std::thread;
+
+use serde::Deserialize;
+
+use crate::util::timestamp::parse_utc_timestamp;
+
+// EDDN relays market snapshots uploaded by other players.
+// applying them between dump refreshes keeps prices fresh, so we don't fly to stale markets.
+//
+// messages can come from:
+//  - a newline-delimited JSON file, one EDDN message per line
+//  - stdin, with a source of "-", so a relay client can be piped in.
+//    prompts also read stdin, so this is meant for --watch or --autoaccept
+//  - a ZeroMQ relay (e.g. tcp://eddn.edcd.io:9500), with the eddn-zmq feature
+
+pub const COMMODITY_SCHEMA: &'static str = "https://eddn.edcd.io/schemas/commodity/3";
+
+#[derive(Deserialize, Debug, Clone)]
+struct EddnEnvelope {
+    #[serde(rename = "$schemaRef")]
+    schema_ref: String,
+    message: serde_json::Value,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct EddnCommodityMessage {
+    pub system_name: String,
+    pub station_name: String,
+    pub market_id: Option<u64>,
+    pub timestamp: String,
+    pub commodities: Vec<EddnCommodity>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct EddnCommodity {
+    pub name: String,
+    pub buy_price: u32,
+    pub sell_price: u32,
+    pub stock: u32,
+    pub demand: u32,
+}
+
+impl EddnCommodityMessage {
+    pub fn unix_seconds(&self) -> Option<i64> {
+        parse_utc_timestamp(&self.timestamp)
+    }
+}
+
+// returns the commodity message on the line, if it is one.
+// other schemas, and the /test variants of the commodity schema, are ignored
+pub fn parse_message(line: &str) -> Option<EddnCommodityMessage> {
+    let envelope: EddnEnvelope = match serde_json::from_str(line) {
+        Ok(e) => e,
+        Err(_) => {
+            return None;
+        }
+    };
+
+    if envelope.schema_ref != COMMODITY_SCHEMA {
+        return None;
+    }
+
+    serde_json::from_value(envelope.message).ok()
+}
+
+// reads messages on a background thread, so the search loop can pick up whatever has arrived
+pub struct EddnListener {
+    receiver: Receiver<EddnCommodityMessage>,
+}
+
+impl EddnListener {
+    pub fn open(source: &String) -> EddnListener {
+        if source == "-" {
+            return EddnListener::from_reader(BufReader::new(io::stdin()));
+        }
+
+        if source.starts_with("tcp://") {
+            return EddnListener::subscribe(source);
+        }
+
+        match File::open(source) {
+            Ok(file) => EddnListener::from_reader(BufReader::new(file)),
+            Err(reason) => panic!("Failed to open EDDN source {}, reason: {}", source, reason),
+        }
+    }
+
+    pub fn from_reader<R>(reader: R) -> EddnListener
+    where
+        R: BufRead + Send + 'static,
+    {
+        let (sender, receiver) = channel();
+
+        thread::spawn(move || {
+            for line in reader.lines() {
+                let line = match line {
+                    Ok(l) => l,
+                    Err(_) => break,
+                };
+
+                if let Some(message) = parse_message(&line) {
+                    if sender.send(message).is_err() {
+                        break;
+                    }
+                }
+            }
+        });
+
+        EddnListener { receiver: receiver }
+    }
+
+    #[cfg(feature = "eddn-zmq")]
+    fn subscribe(endpoint: &String) -> EddnListener {
+        let (sender, receiver) = channel();
+        let endpoint = endpoint.clone();
+
+        thread::spawn(move || relay(endpoint, sender));
+
+        EddnListener { receiver: receiver }
+    }
+
+    #[cfg(not(feature = "eddn-zmq"))]
+    fn subscribe(endpoint: &String) -> EddnListener {
+        panic!(
+            "Cannot connect to {} - ZeroMQ support requires building with --features eddn-zmq.  \
+             Pipe a relay client into --eddn - instead.",
+            endpoint
+        );
+    }
+
+    // all messages received since the last call
+    pub fn drain(&self) -> Vec<EddnCommodityMessage> {
+        self.receiver.try_iter().collect()
+    }
+}
+
+// the relay publishes zlib-compressed messages to every subscriber
+#[cfg(feature = "eddn-zmq")]
+fn relay(endpoint: String, sender: std::sync::mpsc::Sender<EddnCommodityMessage>) {
+    use flate2::read::ZlibDecoder;
+    use std::io::Read;
+
+    let context = zmq::Context::new();
+    let socket = match context.socket(zmq::SUB) {
+        Ok(s) => s,
+        Err(reason) => panic!("Failed to create EDDN socket: {}", reason),
+    };
+
+    if let Err(reason) = socket.connect(&endpoint) {
+        panic!("Failed to connect to EDDN relay {}: {}", endpoint, reason);
+    }
+
+    socket.set_subscribe(b"").unwrap();
+
+    loop {
+        let bytes = match socket.recv_bytes(0) {
+            Ok(b) => b,
+            Err(_) => break,
+        };
+
+        let mut text = String::new();
+        if ZlibDecoder::new(&bytes[..])
+            .read_to_string(&mut text)
+            .is_err()
+        {
+            continue;
+        }
+
+        if let Some(message) = parse_message(&text) {
+            if sender.send(message).is_err() {
+                break;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::source::FixtureSource;
+    use crate::data::filter::FilterOptions;
+    use crate::data::trader::ShipSize;
+    use crate::data::Universe;
+    use std::io::Cursor;
+
+    fn message(schema: &str, market_id: u64, timestamp: &str, buy: u32, sell: u32) -> String {
+        format!(
+            "{{\"$schemaRef\":\"{}\",\"header\":{{\"uploaderID\":\"test\",\"softwareName\":\"test\",\"softwareVersion\":\"1\"}},\
+             \"message\":{{\"systemName\":\"Chono\",\"stationName\":\"Siddha Ring\",\"marketId\":{},\"timestamp\":\"{}\",\
+             \"commodities\":[{{\"name\":\"gold\",\"meanPrice\":9400,\"buyPrice\":{},\"stock\":0,\"stockBracket\":0,\
+             \"sellPrice\":{},\"demand\":5000,\"demandBracket\":2}}]}}}}",
+            schema, market_id, timestamp, buy, sell
+        )
+    }
+
+    #[test]
+    fn test_parse_message() {
+        let line = message(COMMODITY_SCHEMA, 3223343626, "2020-06-14T12:00:00Z", 0, 10500);
+        let parsed = parse_message(&line).expect("commodity message should parse");
+
+        assert_eq!("Chono", parsed.system_name);
+        assert_eq!(Some(3223343626), parsed.market_id);
+        assert_eq!(10500, parsed.commodities[0].sell_price);
+        assert_eq!(Some(1592136000), parsed.unix_seconds());
+
+        let test_line = message(
+            "https://eddn.edcd.io/schemas/commodity/3/test",
+            3223343626,
+            "2020-06-14T12:00:00Z",
+            0,
+            10500,
+        );
+        assert!(parse_message(&test_line).is_none());
+        assert!(parse_message("not json").is_none());
+    }
+
+    #[test]
+    fn test_apply_relay_messages() {
+        let mut universe = Universe::from_source(
+            &FixtureSource::sample(),
+            &FilterOptions::new(ShipSize::Large),
+        );
+
+        // a local stand-in for the relay - an older message arrives after a newer one
+        let lines = vec![
+            message(COMMODITY_SCHEMA, 3223343626, "2020-06-14T12:00:00Z", 0, 10500),
+            message(COMMODITY_SCHEMA, 3223343626, "2020-06-14T11:00:00Z", 0, 9900),
+        ];
+        let listener = EddnListener::from_reader(Cursor::new(lines.join("\n")));
+        let messages: Vec<EddnCommodityMessage> = listener.receiver.iter().collect();
+        assert_eq!(2, messages.len());
+
+        assert_eq!(Some((20, 1)), universe.apply_eddn_message(&messages[0]));
+        assert_eq!(Some((20, 0)), universe.apply_eddn_message(&messages[1]));
+
+        let siddha = universe.get_station(20).unwrap();
+        assert_eq!(10500, siddha.listings[0].sell_price);
+        assert_eq!(1592136000, siddha.listings[0].collected_at);
+    }
+}
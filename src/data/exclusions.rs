@@ -0,0 +1,141 @@
+use serde::Deserialize;
+use serde::Serialize;
+
+use std::path::PathBuf;
+
+use crate::data::trader::commodity_symbol;
+use crate::data::trader::Commodity;
+use crate::data::trader::Station;
+use crate::data::trader::System;
+use crate::persist::*;
+
+// places and goods the player never wants suggested, stored in ~/.elite_trader/exclusions.json
+// names are matched without case.  stations can be given as 'Station', or 'System/Station'
+// if the name isn't unique.
+//
+// example:
+// {
+//   "systems": ["Robigo"],
+//   "stations": ["Chono/Siddha Ring"],
+//   "commodities": ["Imperial Slaves"],
+//   "categories": ["Weapons"],
+//   "factions": ["Kumo Crew"]
+// }
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(default)]
+pub struct Exclusions {
+    pub systems: Vec<String>,
+    pub stations: Vec<String>,
+    pub commodities: Vec<String>,
+    pub categories: Vec<String>,
+    // systems controlled by these factions are avoided
+    pub factions: Vec<String>,
+}
+
+impl Exclusions {
+    pub fn get_path() -> PathBuf {
+        get_base_directory().join("exclusions.json")
+    }
+
+    pub fn load() -> Exclusions {
+        let path = Self::get_path();
+
+        if path.exists() {
+            read_json(&path)
+        } else {
+            Exclusions::default()
+        }
+    }
+
+    pub fn save(&self) {
+        write_json(&Self::get_path(), self);
+    }
+
+    pub fn add_station(&mut self, system: &System, station: &Station) {
+        if self.excludes_station(system, station) {
+            return;
+        }
+
+        self.stations
+            .push(format!("{}/{}", system.system_name, station.station_name));
+    }
+
+    pub fn excludes_system(&self, system: &System) -> bool {
+        if contains(&self.systems, &system.system_name) {
+            return true;
+        }
+
+        match system.controlling_faction {
+            Some(ref faction) => contains(&self.factions, faction),
+            None => false,
+        }
+    }
+
+    pub fn excludes_station(&self, system: &System, station: &Station) -> bool {
+        self.stations.iter().any(|e| match e.rfind('/') {
+            Some(i) => {
+                e[..i].trim().eq_ignore_ascii_case(&system.system_name)
+                    && e[i + 1..].trim().eq_ignore_ascii_case(&station.station_name)
+            }
+            None => e.trim().eq_ignore_ascii_case(&station.station_name),
+        })
+    }
+
+    pub fn excludes_commodity(&self, commodity: &Commodity) -> bool {
+        let symbol = commodity.symbol();
+
+        self.commodities
+            .iter()
+            .any(|e| commodity_symbol(e) == symbol)
+            || contains(&self.categories, &commodity.category)
+    }
+}
+
+fn contains(names: &Vec<String>, name: &String) -> bool {
+    names.iter().any(|e| e.trim().eq_ignore_ascii_case(name))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::filter::FilterOptions;
+    use crate::data::source::FixtureSource;
+    use crate::data::trader::ShipSize;
+    use crate::data::Universe;
+
+    #[test]
+    fn test_exclusions() {
+        let mut exclusions: Exclusions = serde_json::from_str(
+            r#"{"stations": ["Giger Hub"], "commodities": ["explosives"], "factions": ["Kumo Crew"]}"#,
+        )
+        .unwrap();
+
+        let mut filter = FilterOptions::new(ShipSize::Large);
+        filter.exclusions = exclusions.clone();
+        let universe = Universe::from_source(&FixtureSource::sample(), &filter);
+
+        let peraesii = universe.get_system(1).unwrap();
+        let giger = universe.get_station(10).unwrap();
+        assert!(exclusions.excludes_station(peraesii, giger));
+        assert!(giger.is_excluded);
+
+        let explosives = &giger.listings[1].commodity;
+        assert!(exclusions.excludes_commodity(explosives));
+        assert!(!exclusions.excludes_commodity(&giger.listings[0].commodity));
+
+        let chono = universe.get_system(2).unwrap();
+        let siddha = universe.get_station(20).unwrap();
+        assert!(!siddha.is_excluded);
+        assert!(!exclusions.excludes_system(chono));
+
+        let mut kumo_chono = chono.clone();
+        kumo_chono.controlling_faction = Some("kumo crew".to_string());
+        assert!(exclusions.excludes_system(&kumo_chono));
+
+        exclusions.add_station(chono, siddha);
+        assert_eq!("Chono/Siddha Ring", exclusions.stations[1]);
+        assert!(exclusions.excludes_station(chono, siddha));
+        assert!(!exclusions.excludes_station(peraesii, siddha));
+    }
+}
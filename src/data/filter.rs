@@ -0,0 +1,113 @@
+use serde::Deserialize;
+use serde::Serialize;
+
+use crate::data::exclusions::Exclusions;
+use crate::data::trader::ShipSize;
+use crate::data::trader::Station;
+use crate::data::trader::System;
+
+// a permit-locked system the commander can enter, by name or eddb system id
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(untagged)]
+pub enum Permit {
+    Id(u32),
+    Name(String),
+}
+
+impl Permit {
+    pub fn matches(&self, system: &System) -> bool {
+        match self {
+            &Permit::Id(id) => system.system_id == id,
+            &Permit::Name(ref name) => system.system_name.eq_ignore_ascii_case(name.trim()),
+        }
+    }
+}
+
+// based on lots of work in Mathematica, station sell prices significantly change,
+//   about 22 days after they collected.
+// stations which haven't been updated in this time are excluded,
+// this should prevent the player from being sent to stations for a loss
+//todo: dynamically scale buy/sell prices based on confidence during trade calculations
+const MAX_MARKET_AGE: u64 = 22 * 24 * 3600;
+
+// the parts of the universe the player wants routes through.
+// stations which don't pass are marked as excluded when the universe is loaded,
+// so the search never sends the player there
+#[derive(Clone)]
+pub struct FilterOptions {
+    pub ship_size: ShipSize,
+    // surface ports and settlements.  these need Horizons or Odyssey
+    pub planetary: bool,
+    // fleet carriers move, and their owners change the orders whenever they like.
+    // they are only included if their market was updated within this many seconds
+    pub carrier_max_age: Option<u64>,
+    // permit-locked systems are excluded, except these
+    pub permits: Vec<Permit>,
+    // adds black market offers for illegal goods
+    pub smuggling: bool,
+    // the player's never-suggest list
+    pub exclusions: Exclusions,
+    // only stations which sell fuel
+    pub require_refuel: bool,
+    // faction states to stay away from, e.g. Lockdown or Civil War
+    pub avoid_states: Vec<String>,
+}
+
+impl FilterOptions {
+    pub fn new(ship_size: ShipSize) -> FilterOptions {
+        FilterOptions {
+            ship_size: ship_size,
+            planetary: false,
+            carrier_max_age: None,
+            permits: Vec::new(),
+            smuggling: false,
+            exclusions: Exclusions::default(),
+            require_refuel: false,
+            avoid_states: Vec::new(),
+        }
+    }
+
+    pub fn can_enter(&self, system: &System) -> bool {
+        !system.needs_permit || self.permits.iter().any(|e| e.matches(system))
+    }
+
+    // whether the station can be a destination
+    pub fn includes(&self, station: &Station, now: u64) -> bool {
+        if station.max_landing_pad_size < self.ship_size {
+            return false;
+        }
+
+        if station.is_planetary && !self.planetary {
+            return false;
+        }
+
+        if self.require_refuel && station.has_refuel == Some(false) {
+            return false;
+        }
+
+        if let Some(market_updated_at) = station.market_updated_at {
+            if now.saturating_sub(market_updated_at) > MAX_MARKET_AGE {
+                return false;
+            }
+        }
+
+        match (station.is_fleet_carrier, self.carrier_max_age) {
+            (false, _) => true,
+            (true, Some(max_age)) => station.market_age(now) <= max_age,
+            (true, None) => false,
+        }
+    }
+
+    // whether the station, or the system it is in, is in one of the states the player avoids
+    pub fn avoids(&self, system: &System, station: &Station) -> bool {
+        station
+            .states
+            .iter()
+            .chain(system.states.iter())
+            .any(|state| {
+                self.avoid_states
+                    .iter()
+                    .any(|e| e.trim().eq_ignore_ascii_case(state))
+            })
+    }
+}
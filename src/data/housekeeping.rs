@@ -0,0 +1,168 @@
+use std::collections::HashMap;
+use std::fs::remove_file;
+use std::path::Path;
+
+use crate::data::adjustments::*;
+use crate::data::cache;
+use crate::data::time_adjustment::TIME_ADJUSTMENT_HISTORY;
+use crate::data::universe::get_cachefile_loc;
+use crate::data::Universe;
+use crate::util::num_unit::NumericUnit;
+
+// cleans up files in ~/.elite_trader which no longer affect the search:
+//  - JSON caches from older versions, once the binary cache exists
+//  - price adjustments which are older than the listing they patch
+//  - price adjustments which are completely overwritten by a newer adjustment
+//  - time adjustments beyond the history the universe keeps
+// the adjustments are removed by compacting the adjustment log
+
+#[derive(Default)]
+pub struct HousekeepingReport {
+    pub removed_caches: usize,
+    pub expired_prices: usize,
+    pub superseded_prices: usize,
+    pub pruned_times: usize,
+    pub bytes_reclaimed: u64,
+}
+
+impl HousekeepingReport {
+    pub fn items_removed(&self) -> usize {
+        self.removed_caches + self.expired_prices + self.superseded_prices + self.pruned_times
+    }
+
+    pub fn print(&self) {
+        println!("housekeeping:");
+        println!("\t{} old universe caches removed", self.removed_caches);
+        println!(
+            "\t{} price adjustments expired by newer market data",
+            self.expired_prices
+        );
+        println!(
+            "\t{} price adjustments replaced by newer adjustments",
+            self.superseded_prices
+        );
+        println!("\t{} old time adjustments removed", self.pruned_times);
+        println!(
+            "\t{} reclaimed",
+            NumericUnit::new_string(self.bytes_reclaimed, &"B".to_string())
+        );
+    }
+
+    fn remove(&mut self, path: &Path) -> bool {
+        let len = path.metadata().map(|m| m.len()).unwrap_or(0);
+
+        match remove_file(path) {
+            Ok(_) => {
+                self.bytes_reclaimed += len;
+                true
+            }
+            Err(reason) => {
+                println!(
+                    "Failed to remove {}: {}",
+                    path.to_str().unwrap_or("<unknown>"),
+                    reason
+                );
+                false
+            }
+        }
+    }
+}
+
+// legacy caches are only removed once the new cache exists.
+// adjustments for stations which aren't in the universe (e.g. dropped from the dumps) are kept
+pub fn run(universe: &Universe) -> HousekeepingReport {
+    let mut report = HousekeepingReport::default();
+    let cachefile = get_cachefile_loc();
+
+    if cachefile.exists() {
+        if let Some(dir) = cachefile.parent() {
+            for path in cache::legacy_caches(dir) {
+                if report.remove(&path) {
+                    report.removed_caches += 1;
+                }
+            }
+        }
+    }
+
+    compact_adjustments(universe, &mut report);
+
+    report
+}
+
+// rewrites the adjustment log without the records which no longer apply
+fn compact_adjustments(universe: &Universe, report: &mut HousekeepingReport) {
+    let log = AdjustmentLog::load();
+    let mut keep = vec![true; log.records.len()];
+
+    // fields which newer adjustments have already set, by station and commodity
+    let mut covered: HashMap<(u32, u16), (bool, bool, bool)> = HashMap::new();
+    let mut times = Vec::new();
+
+    // newest first
+    for (i, record) in log.records.iter().enumerate().rev() {
+        let price = match record {
+            &AdjustmentRecord::Price(ref price) => price,
+            &AdjustmentRecord::Time(ref time) => {
+                times.push((time.timestamp, i));
+                continue;
+            }
+        };
+
+        let listing = universe.get_listing_by_index(
+            universe
+                .get_index()
+                .get_index_listing(price.station_id, price.commodity_id),
+        );
+
+        if let Some(listing) = listing {
+            if price.is_superseded_by(listing) {
+                keep[i] = false;
+                report.expired_prices += 1;
+                continue;
+            }
+        }
+
+        let key = (price.station_id, price.commodity_id);
+        let fields = covered.entry(key).or_insert((false, false, false));
+
+        let is_covered = (price.buy_price.is_none() || fields.0)
+            && (price.supply.is_none() || fields.1)
+            && (price.sell_price.is_none() || fields.2);
+
+        if is_covered {
+            keep[i] = false;
+            report.superseded_prices += 1;
+            continue;
+        }
+
+        fields.0 |= price.buy_price.is_some();
+        fields.1 |= price.supply.is_some();
+        fields.2 |= price.sell_price.is_some();
+    }
+
+    // the universe only keeps the newest time adjustments
+    times.sort_by(|a, b| b.0.cmp(&a.0));
+    for &(_, i) in times.iter().skip(TIME_ADJUSTMENT_HISTORY) {
+        keep[i] = false;
+        report.pruned_times += 1;
+    }
+
+    if keep.iter().all(|&e| e) && log.skipped == 0 {
+        return;
+    }
+
+    let path = get_log_path();
+    let len_before = path.metadata().map(|m| m.len()).unwrap_or(0);
+
+    let records: Vec<AdjustmentRecord> = log
+        .records
+        .into_iter()
+        .zip(keep.into_iter())
+        .filter(|&(_, keep)| keep)
+        .map(|(record, _)| record)
+        .collect();
+    rewrite_adjustments(&records);
+
+    let len_after = path.metadata().map(|m| m.len()).unwrap_or(0);
+    report.bytes_reclaimed += len_before.saturating_sub(len_after);
+}
@@ -0,0 +1,693 @@
+use std::fs::read_dir;
+use std::fs::File;
+use std::io::BufRead;
+use std::io::BufReader;
+use std::path::Path;
+use std::path::PathBuf;
+
+use std::env::home_dir;
+
+use crate::data::price_adjustment::PriceAdjustment;
+use crate::data::trader::*;
+use crate::data::universe::Universe;
+use crate::persist;
+use crate::util::timestamp::parse_utc_timestamp;
+use serde::Deserialize;
+use serde::Serialize;
+
+// reads the Player Journal which the game writes to
+//   %USERPROFILE%\Saved Games\Frontier Developments\Elite Dangerous
+// the journal is a set of Journal.*.log files with one JSON event per line,
+// plus a few snapshot files (Market.json, Cargo.json, Status.json) which are overwritten in-place.
+
+pub fn default_journal_dir() -> PathBuf {
+    let home = home_dir().unwrap_or(Path::new(".").to_path_buf());
+    home.join("Saved Games")
+        .join("Frontier Developments")
+        .join("Elite Dangerous")
+}
+
+#[derive(Debug, Clone)]
+pub struct JournalData {
+    pub commander: Option<String>,
+    pub system: Option<String>,
+    pub station: Option<String>,
+    pub market_id: Option<u64>,
+    pub docked: bool,
+    pub credits: Option<u64>,
+    pub cargo_capacity: Option<u32>,
+    pub max_jump_range: Option<f64>,
+    // with a full hold and full tanks
+    pub laden_jump_range: Option<f64>,
+    pub ship: Option<String>,
+    pub market: Option<JournalMarket>,
+    pub cargo: Option<JournalCargo>,
+}
+
+impl JournalData {
+    pub fn generate_opt(base_path: &Option<String>) -> Option<JournalData> {
+        match base_path {
+            &Some(ref path) => Self::generate(&path),
+            &None => None,
+        }
+    }
+
+    pub fn generate(base_path: &String) -> Option<JournalData> {
+        let dir = Path::new(base_path);
+
+        let journal_path = match Self::latest_journal_file(dir) {
+            Some(path) => path,
+            None => {
+                println!(
+                    "Journal Error: No Journal.*.log files were found in {}",
+                    base_path
+                );
+                return None;
+            }
+        };
+
+        let file = match File::open(&journal_path) {
+            Ok(f) => f,
+            Err(reason) => {
+                println!(
+                    "Journal Error: Could not open {}: {}",
+                    journal_path.to_str().unwrap_or("<unknown>"),
+                    reason
+                );
+                return None;
+            }
+        };
+
+        let mut data = JournalData {
+            commander: None,
+            system: None,
+            station: None,
+            market_id: None,
+            docked: false,
+            credits: None,
+            cargo_capacity: None,
+            max_jump_range: None,
+            laden_jump_range: None,
+            ship: None,
+            market: None,
+            cargo: None,
+        };
+
+        for line in BufReader::new(file).lines() {
+            let line = match line {
+                Ok(l) => l,
+                Err(_) => {
+                    break;
+                }
+            };
+
+            // the game may be writing the last line while we read it.
+            // unparseable lines are skipped
+            if let Ok(event) = serde_json::from_str::<JournalEvent>(&line) {
+                data.apply_event(&event);
+            }
+        }
+
+        data.market = Self::read_snapshot(dir, "Market.json");
+        data.cargo = Self::read_snapshot(dir, "Cargo.json");
+
+        // Status.json is rewritten several times per second, so it has the most accurate balance
+        let status: Option<JournalStatus> = Self::read_snapshot(dir, "Status.json");
+        if let Some(balance) = status.and_then(|s| s.balance) {
+            data.credits = Some(balance);
+        }
+
+        Some(data)
+    }
+
+    pub fn apply_event(&mut self, event: &JournalEvent) {
+        match event {
+            &JournalEvent::LoadGame(ref e) => {
+                self.commander = Some(e.commander.clone());
+                self.credits = Some(e.credits);
+
+                // the game writes a Loadout after LoadGame.
+                // if the player switched ships, the old loadout no longer applies
+                let ship = e.ship.to_lowercase();
+                if self.ship.as_ref() != Some(&ship) {
+                    self.cargo_capacity = None;
+                    self.max_jump_range = None;
+                    self.laden_jump_range = None;
+                }
+                self.ship = Some(ship);
+            }
+            &JournalEvent::Loadout(ref e) => {
+                self.ship = Some(e.ship.to_lowercase());
+                self.cargo_capacity = Some(e.cargo_capacity);
+                self.max_jump_range = e.max_jump_range;
+                self.laden_jump_range = e.laden_jump_range();
+            }
+            &JournalEvent::Location(ref e) => {
+                self.system = Some(e.star_system.clone());
+                self.docked = e.docked;
+                if e.docked {
+                    self.station = e.station_name.clone();
+                    self.market_id = e.market_id;
+                } else {
+                    self.station = None;
+                    self.market_id = None;
+                }
+            }
+            &JournalEvent::Docked(ref e) => {
+                self.system = Some(e.star_system.clone());
+                self.station = Some(e.station_name.clone());
+                self.market_id = e.market_id;
+                self.docked = true;
+            }
+            &JournalEvent::Undocked(_) => {
+                self.docked = false;
+            }
+            &JournalEvent::FsdJump(ref e) => {
+                self.system = Some(e.star_system.clone());
+                self.station = None;
+                self.market_id = None;
+                self.docked = false;
+            }
+            &JournalEvent::MarketBuy(ref e) => {
+                self.credits = self.credits.map(|c| c.saturating_sub(e.total_cost));
+            }
+            &JournalEvent::MarketSell(ref e) => {
+                self.credits = self.credits.map(|c| c + e.total_sale);
+            }
+            &JournalEvent::Other => {}
+        }
+    }
+
+    pub fn ship_size(&self) -> Option<ShipSize> {
+        self.ship.as_ref().and_then(|ship| ShipSize::from_hull(ship))
+    }
+
+    // the ship's cargo as commodity symbols and tons, from Cargo.json.
+    // mission cargo is delivered rather than sold, and miners keep their limpets
+    pub fn held_cargo(&self) -> Vec<(String, u32)> {
+        self.ship_cargo()
+            .iter()
+            .filter(|e| e.is_sellable() && e.count > 0)
+            .map(|e| (e.name.clone(), e.count))
+            .collect()
+    }
+
+    // the tons of mission cargo and limpets, which still take up room in the hold
+    pub fn reserved_tons(&self) -> u32 {
+        self.ship_cargo()
+            .iter()
+            .filter(|e| !e.is_sellable())
+            .map(|e| e.count)
+            .sum()
+    }
+
+    // Cargo.json also describes the SRV's hold
+    fn ship_cargo(&self) -> &[JournalCargoItem] {
+        match self.cargo {
+            Some(ref cargo) if cargo.vessel.as_ref().map_or(true, |v| v == "Ship") => {
+                &cargo.inventory
+            }
+            _ => &[],
+        }
+    }
+
+    pub fn latest_journal_file(dir: &Path) -> Option<PathBuf> {
+        let entries = match read_dir(dir) {
+            Ok(entries) => entries,
+            Err(_) => {
+                return None;
+            }
+        };
+
+        let mut journals = Vec::new();
+        for entry in entries {
+            let entry = match entry {
+                Ok(e) => e,
+                Err(_) => {
+                    continue;
+                }
+            };
+
+            let filename = entry.file_name().to_str().unwrap_or("").to_string();
+            if !filename.starts_with("Journal.") || !filename.ends_with(".log") {
+                continue;
+            }
+
+            // the filename format changed in 2022 (Journal.YYMMDDHHMMSS.01.log to Journal.YYYY-MM-DDTHHMMSS.01.log),
+            // so the modification time is the only reliable ordering
+            let modified = entry.metadata().and_then(|m| m.modified()).ok();
+            journals.push((modified, filename, entry.path()));
+        }
+
+        journals.sort_by(|a, b| a.0.cmp(&b.0).then(a.1.cmp(&b.1)));
+        journals.pop().map(|e| e.2)
+    }
+
+    fn read_snapshot<T>(dir: &Path, filename: &str) -> Option<T>
+    where
+        T: serde::de::DeserializeOwned,
+    {
+        let path = dir.join(filename);
+        match File::open(&path) {
+            Ok(mut f) => {
+                let text = persist::read_text_from_file(&mut f);
+                // the game truncates and rewrites these files, so we may see an empty file
+                serde_json::from_str(&text).ok()
+            }
+            Err(_) => None,
+        }
+    }
+
+    pub fn apply_market_adjustments<'a>(
+        &'a self,
+        universe: &'a mut Universe,
+    ) -> Option<JournalStationUpdate<'a>> {
+        let update = self.update_market(universe)?;
+
+        for change in &update.changes {
+            let listing = &change.new_listing;
+            let adjustment = PriceAdjustment::new(
+                listing.supply,
+                listing.buy_price,
+                listing.sell_price,
+                listing,
+            );
+            adjustment.save();
+        }
+
+        Some(update)
+    }
+
+    // copies the Market.json prices into the universe, without saving adjustments
+    fn update_market<'a>(&'a self, universe: &'a mut Universe) -> Option<JournalStationUpdate<'a>> {
+        let market = match self.market {
+            Some(ref m) => m,
+            None => {
+                return None;
+            }
+        };
+
+        let mut updates = Vec::new();
+
+        // dumps without market ids fall back to the station name
+        let station = match universe.get_station_by_market_id(market.market_id).is_some() {
+            true => universe.get_station_by_market_id_mut(market.market_id),
+            false => universe.get_station_by_name_mut(&market.star_system, &market.station_name),
+        };
+
+        if let Some(station) = station {
+            for listing in station.listings.iter_mut() {
+                let listing_symbol = listing.commodity.symbol();
+
+                for item in &market.items {
+                    if item.symbol() != listing_symbol {
+                        continue;
+                    }
+
+                    if listing.buy_price != item.buy_price
+                        || listing.sell_price != item.sell_price
+                        || listing.supply != item.stock
+                    {
+                        let listing_old = listing.clone();
+
+                        listing.buy_price = item.buy_price;
+                        listing.sell_price = item.sell_price;
+                        listing.supply = item.stock;
+
+                        updates.push(JournalListingUpdate {
+                            old_listing: listing_old,
+                            new_listing: listing.clone(),
+                        });
+                    }
+
+                    break;
+                }
+            }
+
+            Some(JournalStationUpdate {
+                station: station,
+                changes: updates,
+            })
+        } else {
+            None
+        }
+    }
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(tag = "event")]
+pub enum JournalEvent {
+    LoadGame(JournalLoadGame),
+    Loadout(JournalLoadout),
+    Location(JournalLocation),
+    Docked(JournalDocked),
+    Undocked(JournalUndocked),
+    #[serde(rename = "FSDJump")]
+    FsdJump(JournalFsdJump),
+    MarketBuy(JournalMarketBuy),
+    MarketSell(JournalMarketSell),
+    #[serde(other)]
+    Other,
+}
+
+impl JournalEvent {
+    pub fn timestamp(&self) -> Option<&String> {
+        match self {
+            &JournalEvent::LoadGame(ref e) => Some(&e.timestamp),
+            &JournalEvent::Loadout(ref e) => Some(&e.timestamp),
+            &JournalEvent::Location(ref e) => Some(&e.timestamp),
+            &JournalEvent::Docked(ref e) => Some(&e.timestamp),
+            &JournalEvent::Undocked(ref e) => Some(&e.timestamp),
+            &JournalEvent::FsdJump(ref e) => Some(&e.timestamp),
+            &JournalEvent::MarketBuy(ref e) => Some(&e.timestamp),
+            &JournalEvent::MarketSell(ref e) => Some(&e.timestamp),
+            &JournalEvent::Other => None,
+        }
+    }
+
+    pub fn unix_seconds(&self) -> Option<i64> {
+        self.timestamp().and_then(|t| parse_utc_timestamp(t))
+    }
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct JournalLoadGame {
+    pub timestamp: String,
+    #[serde(rename = "Commander")]
+    pub commander: String,
+    #[serde(rename = "Ship")]
+    pub ship: String,
+    #[serde(rename = "Credits")]
+    pub credits: u64,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct JournalLoadout {
+    pub timestamp: String,
+    #[serde(rename = "Ship")]
+    pub ship: String,
+    #[serde(rename = "CargoCapacity", default)]
+    pub cargo_capacity: u32,
+    // the unladen range - no cargo, and only the fuel for one jump
+    #[serde(rename = "MaxJumpRange")]
+    pub max_jump_range: Option<f64>,
+    // the hull and modules, without fuel or cargo
+    #[serde(rename = "UnladenMass")]
+    pub unladen_mass: Option<f64>,
+    #[serde(rename = "FuelCapacity")]
+    pub fuel_capacity: Option<JournalFuelCapacity>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct JournalFuelCapacity {
+    #[serde(rename = "Main")]
+    pub main: f64,
+}
+
+impl JournalLoadout {
+    // the range shrinks in proportion to the ship's mass, so we scale the unladen range
+    // by the mass of a full hold and full tanks.  the unladen range still carries the fuel
+    // for one jump, so this comes out a little short, which is the safe side
+    pub fn laden_jump_range(&self) -> Option<f64> {
+        let max_jump_range = self.max_jump_range?;
+        let unladen_mass = self.unladen_mass?;
+        let fuel = self.fuel_capacity.as_ref()?.main;
+
+        if unladen_mass <= 0f64 {
+            return None;
+        }
+
+        Some(max_jump_range * unladen_mass / (unladen_mass + self.cargo_capacity as f64 + fuel))
+    }
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct JournalLocation {
+    pub timestamp: String,
+    #[serde(rename = "StarSystem")]
+    pub star_system: String,
+    #[serde(rename = "Docked", default)]
+    pub docked: bool,
+    #[serde(rename = "StationName")]
+    pub station_name: Option<String>,
+    #[serde(rename = "MarketID")]
+    pub market_id: Option<u64>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct JournalDocked {
+    pub timestamp: String,
+    #[serde(rename = "StarSystem")]
+    pub star_system: String,
+    #[serde(rename = "StationName")]
+    pub station_name: String,
+    #[serde(rename = "MarketID")]
+    pub market_id: Option<u64>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct JournalUndocked {
+    pub timestamp: String,
+    #[serde(rename = "StationName")]
+    pub station_name: String,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct JournalFsdJump {
+    pub timestamp: String,
+    #[serde(rename = "StarSystem")]
+    pub star_system: String,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct JournalMarketBuy {
+    pub timestamp: String,
+    #[serde(rename = "MarketID")]
+    pub market_id: u64,
+    #[serde(rename = "Type")]
+    pub commodity: String,
+    #[serde(rename = "Count")]
+    pub count: u32,
+    #[serde(rename = "BuyPrice")]
+    pub buy_price: u32,
+    #[serde(rename = "TotalCost")]
+    pub total_cost: u64,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct JournalMarketSell {
+    pub timestamp: String,
+    #[serde(rename = "MarketID")]
+    pub market_id: u64,
+    #[serde(rename = "Type")]
+    pub commodity: String,
+    #[serde(rename = "Count")]
+    pub count: u32,
+    #[serde(rename = "SellPrice")]
+    pub sell_price: u32,
+    #[serde(rename = "TotalSale")]
+    pub total_sale: u64,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct JournalMarket {
+    pub timestamp: String,
+    #[serde(rename = "MarketID")]
+    pub market_id: u64,
+    #[serde(rename = "StationName")]
+    pub station_name: String,
+    #[serde(rename = "StarSystem")]
+    pub star_system: String,
+    #[serde(rename = "Items", default)]
+    pub items: Vec<JournalMarketItem>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct JournalMarketItem {
+    #[serde(rename = "Name")]
+    pub name: String,
+    #[serde(rename = "Name_Localised")]
+    pub name_localised: Option<String>,
+    #[serde(rename = "BuyPrice")]
+    pub buy_price: u32,
+    #[serde(rename = "SellPrice")]
+    pub sell_price: u32,
+    #[serde(rename = "Stock")]
+    pub stock: u32,
+    #[serde(rename = "Demand")]
+    pub demand: u32,
+}
+
+impl JournalMarketItem {
+    pub fn symbol(&self) -> String {
+        commodity_symbol(&self.name)
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct JournalCargo {
+    pub timestamp: String,
+    #[serde(rename = "Vessel")]
+    pub vessel: Option<String>,
+    #[serde(rename = "Inventory", default)]
+    pub inventory: Vec<JournalCargoItem>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct JournalCargoItem {
+    #[serde(rename = "Name")]
+    pub name: String,
+    #[serde(rename = "Name_Localised")]
+    pub name_localised: Option<String>,
+    #[serde(rename = "Count")]
+    pub count: u32,
+    #[serde(rename = "MissionID")]
+    pub mission_id: Option<u64>,
+}
+
+impl JournalCargoItem {
+    pub fn is_sellable(&self) -> bool {
+        self.mission_id.is_none() && commodity_symbol(&self.name) != "limpet"
+    }
+}
+
+#[derive(Deserialize, Debug, Clone)]
+struct JournalStatus {
+    #[serde(rename = "Balance")]
+    balance: Option<u64>,
+}
+
+pub struct JournalStationUpdate<'a> {
+    pub station: &'a Station,
+    pub changes: Vec<JournalListingUpdate>,
+}
+
+pub struct JournalListingUpdate {
+    pub old_listing: Listing,
+    pub new_listing: Listing,
+}
+
+impl JournalListingUpdate {
+    pub fn get_commodity_id(&self) -> u16 {
+        self.old_listing.commodity.commodity_id
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fixture_dir() -> String {
+        Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("src/dummy/journal")
+            .to_str()
+            .unwrap()
+            .to_string()
+    }
+
+    #[test]
+    fn test_journal_player_state() {
+        let data = JournalData::generate(&fixture_dir()).unwrap();
+
+        assert_eq!(data.commander, Some("GitHub".to_string()));
+        assert_eq!(data.system, Some("Peraesii".to_string()));
+        assert_eq!(data.station, Some("Giger Hub".to_string()));
+        assert_eq!(data.market_id, Some(3223343616));
+        assert!(data.docked);
+        assert_eq!(data.ship, Some("type9".to_string()));
+        assert_eq!(data.cargo_capacity, Some(216));
+        assert_eq!(data.max_jump_range, Some(18.52));
+        let laden = data.laden_jump_range.unwrap();
+        assert!((laden - 18.52 * 1004.1 / 1252.1).abs() < 1e-9);
+        assert_eq!(data.ship_size(), Some(ShipSize::Large));
+        // Status.json takes precedence over the balance calculated from market events
+        assert_eq!(data.credits, Some(15250000));
+    }
+
+    #[test]
+    fn test_journal_market_snapshot() {
+        let data = JournalData::generate(&fixture_dir()).unwrap();
+        let market = data.market.unwrap();
+
+        assert_eq!(market.station_name, "Giger Hub");
+        assert_eq!(market.items.len(), 3);
+        assert_eq!(market.items[0].symbol(), "explosives");
+        assert_eq!(market.items[2].symbol(), "agrimedicines");
+        assert_eq!(market.items[2].stock, 4512);
+
+        let cargo = data.cargo.as_ref().unwrap();
+        assert_eq!(cargo.inventory.len(), 1);
+        assert_eq!(cargo.inventory[0].count, 216);
+        assert_eq!(data.held_cargo(), vec![("gold".to_string(), 216)]);
+        assert_eq!(data.reserved_tons(), 0);
+    }
+
+    #[test]
+    fn test_journal_reserved_cargo() {
+        let mut data = JournalData::generate(&fixture_dir()).unwrap();
+
+        // mission cargo and limpets aren't sold, but still take up room
+        let item = |name: &str, count: u32, mission_id: Option<u64>| JournalCargoItem {
+            name: name.to_string(),
+            name_localised: None,
+            count: count,
+            mission_id: mission_id,
+        };
+        let cargo = data.cargo.as_mut().unwrap();
+        cargo.inventory.push(item("gold", 20, Some(611111111)));
+        cargo.inventory.push(item("drones", 16, None));
+
+        assert_eq!(data.held_cargo(), vec![("gold".to_string(), 216)]);
+        assert_eq!(data.reserved_tons(), 36);
+
+        // the SRV's hold isn't the ship's
+        data.cargo.as_mut().unwrap().vessel = Some("SRV".to_string());
+        assert!(data.held_cargo().is_empty());
+        assert_eq!(data.reserved_tons(), 0);
+    }
+
+    #[test]
+    fn test_journal_market_update() {
+        use crate::data::filter::FilterOptions;
+        use crate::data::source::FixtureSource;
+
+        let mut data = JournalData::generate(&fixture_dir()).unwrap();
+        let filter = FilterOptions::new(ShipSize::Large);
+        let mut universe = Universe::from_source(&FixtureSource::sample(), &filter);
+
+        // only explosives are in both Market.json and the universe
+        {
+            let update = data.update_market(&mut universe).unwrap();
+            assert_eq!(update.station.station_id, 10);
+            assert_eq!(update.changes.len(), 1);
+            assert_eq!(update.changes[0].old_listing.buy_price, 200);
+            assert_eq!(update.changes[0].new_listing.buy_price, 260);
+            assert_eq!(update.changes[0].new_listing.supply, 3910);
+        }
+
+        let explosives = &universe.get_station(10).unwrap().listings[1];
+        assert_eq!(explosives.sell_price, 240);
+
+        // nothing changes the second time
+        assert_eq!(data.update_market(&mut universe).unwrap().changes.len(), 0);
+
+        // dumps without market ids are matched by name
+        let mut universe = Universe::from_source(&FixtureSource::sample(), &filter);
+        data.market.as_mut().unwrap().market_id = 1;
+        let update = data.update_market(&mut universe).unwrap();
+        assert_eq!(update.station.station_id, 10);
+        assert_eq!(update.changes.len(), 1);
+
+        let mut universe = Universe::from_source(&FixtureSource::sample(), &filter);
+        data.market.as_mut().unwrap().station_name = "Unknown Port".to_string();
+        assert!(data.update_market(&mut universe).is_none());
+    }
+
+    #[test]
+    fn test_commodity_symbol() {
+        assert_eq!(commodity_symbol("$agriculturalmedicines_name;"), "agrimedicines");
+        assert_eq!(commodity_symbol("Agri-Medicines"), "agrimedicines");
+        assert_eq!(commodity_symbol("H.E. Suits"), "hesuits");
+        assert_eq!(commodity_symbol("drones"), "limpet");
+    }
+}
@@ -0,0 +1,226 @@
+use std::collections::VecDeque;
+use std::fs::File;
+use std::io::Read;
+use std::io::Seek;
+use std::io::SeekFrom;
+use std::path::PathBuf;
+use std::sync::mpsc::Receiver;
+use std::thread;
+use std::time::Duration;
+
+use crate::data::journal::JournalData;
+use crate::data::journal::JournalEvent;
+
+const POLL_INTERVAL_MS: u64 = 500;
+
+// something that happened while we were watching - a journal event, or a line the player typed
+pub enum WatchEvent {
+    Journal(JournalEvent),
+    Input(String),
+}
+
+// tails the active journal file, returning events as the game appends them.
+// the game starts a new journal file each session, so we follow the newest file.
+pub struct JournalWatcher {
+    dir: PathBuf,
+    path: Option<PathBuf>,
+    position: u64,
+    partial: String,
+    pending: VecDeque<JournalEvent>,
+}
+
+impl JournalWatcher {
+    pub fn new(base_path: &String) -> JournalWatcher {
+        let dir = PathBuf::from(base_path);
+        let path = JournalData::latest_journal_file(&dir);
+
+        // we only care about events which happen after the watcher is created
+        let position = path
+            .as_ref()
+            .and_then(|p| p.metadata().ok())
+            .map(|m| m.len())
+            .unwrap_or(0);
+
+        JournalWatcher {
+            dir: dir,
+            path: path,
+            position: position,
+            partial: String::new(),
+            pending: VecDeque::new(),
+        }
+    }
+
+    pub fn poll(&mut self) -> Vec<JournalEvent> {
+        let latest = JournalData::latest_journal_file(&self.dir);
+        if latest != self.path {
+            // a new session started - read the new file from the beginning
+            self.path = latest;
+            self.position = 0;
+            self.partial.clear();
+        }
+
+        let path = match self.path {
+            Some(ref p) => p,
+            None => {
+                return Vec::new();
+            }
+        };
+
+        let mut file = match File::open(path) {
+            Ok(f) => f,
+            Err(_) => {
+                return Vec::new();
+            }
+        };
+
+        if file.seek(SeekFrom::Start(self.position)).is_err() {
+            return Vec::new();
+        }
+
+        let mut text = String::new();
+        let bytes = match file.read_to_string(&mut text) {
+            Ok(n) => n,
+            Err(_) => {
+                return Vec::new();
+            }
+        };
+        self.position += bytes as u64;
+        self.partial.push_str(&text);
+
+        // the last line may still be partially written.  keep it until the newline arrives
+        let complete_len = match self.partial.rfind('\n') {
+            Some(i) => i + 1,
+            None => {
+                return Vec::new();
+            }
+        };
+
+        let complete: String = self.partial.drain(..complete_len).collect();
+        complete
+            .lines()
+            .filter_map(|line| serde_json::from_str::<JournalEvent>(line).ok())
+            .collect()
+    }
+
+    // the next event, if the game has written one
+    fn next_event(&mut self) -> Option<JournalEvent> {
+        if self.pending.is_empty() {
+            let events = self.poll();
+            self.pending.extend(events);
+        }
+
+        self.pending.pop_front()
+    }
+
+    // blocks until the handler accepts an event, and returns that event.
+    // the handler is called for every event, so callers can report progress along the way
+    pub fn wait_for<F>(&mut self, mut handler: F) -> JournalEvent
+    where
+        F: FnMut(&JournalEvent) -> bool,
+    {
+        loop {
+            match self.next_event() {
+                Some(event) => {
+                    if handler(&event) {
+                        return event;
+                    }
+                }
+                None => thread::sleep(Duration::from_millis(POLL_INTERVAL_MS)),
+            }
+        }
+    }
+
+    // like wait_for, but the player can still answer prompts while we wait
+    pub fn wait_for_or_input<F>(&mut self, input: &Receiver<String>, mut handler: F) -> WatchEvent
+    where
+        F: FnMut(&JournalEvent) -> bool,
+    {
+        loop {
+            if let Ok(line) = input.try_recv() {
+                return WatchEvent::Input(line);
+            }
+
+            match self.next_event() {
+                Some(event) => {
+                    if handler(&event) {
+                        return WatchEvent::Journal(event);
+                    }
+                }
+                None => thread::sleep(Duration::from_millis(POLL_INTERVAL_MS)),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::util::test_dir::test_dir;
+    use std::fs::remove_dir_all;
+    use std::fs::OpenOptions;
+    use std::io::Write;
+    use std::path::Path;
+    use std::sync::mpsc::channel;
+
+    const BUY: &'static str = "{ \"timestamp\":\"2020-06-14T12:20:00Z\", \"event\":\"MarketBuy\", \
+         \"MarketID\":3223343616, \"Type\":\"gold\", \"Count\":216, \"BuyPrice\":9000, \
+         \"TotalCost\":1944000 }";
+    const MUSIC: &'static str = "{ \"timestamp\":\"2020-06-14T12:21:00Z\", \"event\":\"Music\", \
+         \"MusicTrack\":\"Exploration\" }";
+
+    fn append(path: &Path, text: &str) {
+        let mut file = OpenOptions::new()
+            .append(true)
+            .create(true)
+            .open(path)
+            .unwrap();
+        file.write_all(text.as_bytes()).unwrap();
+    }
+
+    #[test]
+    fn test_watch_journal() {
+        let dir = test_dir("journal_watch");
+        let first = dir.join("Journal.2020-06-14T120000.01.log");
+        append(&first, &format!("{}\n", MUSIC));
+
+        // events from before the watcher started are skipped
+        let mut watcher = JournalWatcher::new(&dir.to_str().unwrap().to_string());
+        assert!(watcher.poll().is_empty());
+
+        // a line is only read once the game finishes writing it
+        let split = BUY.len() / 2;
+        append(&first, &format!("{}\n{}", MUSIC, &BUY[..split]));
+        assert_eq!(watcher.poll().len(), 1);
+        assert!(watcher.poll().is_empty());
+
+        append(&first, &format!("{}\n", &BUY[split..]));
+        match &watcher.poll()[..] {
+            &[JournalEvent::MarketBuy(ref buy)] => assert_eq!(buy.count, 216),
+            _ => panic!("expected the completed MarketBuy line"),
+        }
+
+        // a new session starts a new file, which is read from the start
+        let second = dir.join("Journal.2020-06-14T130000.01.log");
+        append(&second, &format!("{}\n{}\n", MUSIC, BUY));
+        assert_eq!(watcher.poll().len(), 2);
+
+        // typed lines are returned alongside journal events
+        let (sender, receiver) = channel();
+        sender.send("n".to_string()).unwrap();
+        match watcher.wait_for_or_input(&receiver, |_| true) {
+            WatchEvent::Input(line) => assert_eq!(line, "n"),
+            WatchEvent::Journal(_) => panic!("expected the typed line"),
+        }
+
+        append(&second, &format!("{}\n", BUY));
+        match watcher.wait_for_or_input(&receiver, |e| match e {
+            &JournalEvent::MarketBuy(_) => true,
+            _ => false,
+        }) {
+            WatchEvent::Journal(JournalEvent::MarketBuy(_)) => {}
+            _ => panic!("expected the MarketBuy event"),
+        }
+
+        remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,152 @@
+use crate::data::trader::Commodity;
+use crate::data::trader::Station;
+use crate::data::trader::System;
+
+// which goods a station will seize.
+// the market data only lists the goods a station prohibits, and many dumps leave that list empty.
+// the law actually comes from the jurisdiction - the allegiance and government of the system's controlling faction.
+//
+// this is an approximation of the in-game rules, matched by commodity symbol (see trader::commodity_symbol)
+//
+// it only decides which black market offers to create - a station that lists a commodity on its
+// open market buys it, whatever the tables say
+
+// illegal wherever there is law
+const ILLEGAL_EVERYWHERE: [&'static str; 5] = [
+    "battleweapons",
+    "landmines",
+    "nerveagents",
+    "narcotics",
+    "slaves",
+];
+
+const ILLEGAL_IN_FEDERATION: [&'static str; 2] = ["imperialslaves", "combatstabilisers"];
+const ILLEGAL_IN_ALLIANCE: [&'static str; 1] = ["imperialslaves"];
+const ILLEGAL_IN_THEOCRACY: [&'static str; 5] =
+    ["beer", "wine", "liquor", "bootlegliquor", "tobacco"];
+
+// the goods the station's own market refuses
+pub fn is_prohibited_by_station(commodity: &Commodity, station: &Station) -> bool {
+    station
+        .prohibited_commodities
+        .contains(&commodity.commodity_id)
+}
+
+pub fn is_illegal(commodity: &Commodity, system: &System, station: &Station) -> bool {
+    if is_prohibited_by_station(commodity, station) {
+        return true;
+    }
+
+    let (allegiance, government) = jurisdiction(system, station);
+
+    // anarchies don't enforce anything
+    if government == "anarchy" {
+        return false;
+    }
+
+    let symbol = commodity.symbol();
+    let symbol = &&symbol[..];
+
+    if ILLEGAL_EVERYWHERE.contains(symbol) {
+        return true;
+    }
+
+    let illegal_by_allegiance = match &allegiance[..] {
+        "federation" => ILLEGAL_IN_FEDERATION.contains(symbol),
+        "alliance" => ILLEGAL_IN_ALLIANCE.contains(symbol),
+        _ => false,
+    };
+
+    illegal_by_allegiance || (government == "theocracy" && ILLEGAL_IN_THEOCRACY.contains(symbol))
+}
+
+// the lowercase allegiance and government which set the law at the station.
+// the system's controlling faction is used when the dump has it, otherwise the station's
+pub fn jurisdiction(system: &System, station: &Station) -> (String, String) {
+    let allegiance = system
+        .allegiance
+        .as_ref()
+        .or(station.allegiance.as_ref())
+        .map(|e| e.to_lowercase())
+        .unwrap_or(String::new());
+
+    let government = system
+        .government
+        .as_ref()
+        .or(station.government.as_ref())
+        .map(|e| e.to_lowercase())
+        .unwrap_or(String::new());
+
+    (allegiance, government)
+}
+
+// e.g. 'Federation Democracy, LHS 3447 Blue Dragons'
+pub fn describe_jurisdiction(system: &System, station: &Station) -> String {
+    let allegiance = system.allegiance.as_ref().or(station.allegiance.as_ref());
+    let government = system.government.as_ref().or(station.government.as_ref());
+
+    let mut parts = Vec::new();
+    if let Some(allegiance) = allegiance {
+        parts.push(allegiance.clone());
+    }
+
+    if let Some(government) = government {
+        parts.push(government.clone());
+    }
+
+    let mut description = parts.join(" ");
+    if let Some(ref faction) = system.controlling_faction {
+        if !description.is_empty() {
+            description.push_str(", ");
+        }
+
+        description.push_str(faction);
+    }
+
+    description
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::filter::FilterOptions;
+    use crate::data::source::FixtureSource;
+    use crate::data::trader::ShipSize;
+    use crate::data::Universe;
+
+    fn commodity(id: u16, name: &str) -> Commodity {
+        Commodity {
+            commodity_id: id,
+            commodity_name: name.to_string(),
+            category: "Legal Drugs".to_string(),
+            average_price: None,
+        }
+    }
+
+    #[test]
+    fn test_jurisdiction_legality() {
+        let universe = Universe::from_source(
+            &FixtureSource::sample(),
+            &FilterOptions::new(ShipSize::Large),
+        );
+
+        let siddha = universe.get_station(20).unwrap();
+        let mut chono = universe.get_system(2).unwrap().clone();
+
+        // prohibited by the station's market
+        assert!(is_illegal(&commodity(2, "Explosives"), &chono, siddha));
+
+        assert!(is_illegal(&commodity(100, "Narcotics"), &chono, siddha));
+        assert!(!is_illegal(&commodity(100, "Wine"), &chono, siddha));
+        assert!(!is_illegal(&commodity(100, "Imperial Slaves"), &chono, siddha));
+
+        chono.allegiance = Some("Federation".to_string());
+        chono.government = Some("Theocracy".to_string());
+        assert!(is_illegal(&commodity(100, "Wine"), &chono, siddha));
+        assert!(is_illegal(&commodity(100, "Imperial Slaves"), &chono, siddha));
+
+        chono.government = Some("Anarchy".to_string());
+        assert!(!is_illegal(&commodity(100, "Narcotics"), &chono, siddha));
+        assert_eq!("Federation Anarchy", describe_jurisdiction(&chono, siddha));
+    }
+}
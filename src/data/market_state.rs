@@ -0,0 +1,170 @@
+use crate::data::trader::Station;
+use crate::data::trader::System;
+
+// how much we trust a market, given the states of the station and its system.
+// markets in crisis states close, run dry, or reprice between the dump and our arrival.
+//
+// reliability is the chance the listing is still good when we get there.
+// price_shift is the expected relative change in the sell price, e.g. -0.1 for 10% lower.
+// states are matched without case or spaces, so 'Civil War' and 'CivilWar' are the same.
+// these are rough numbers from watching markets in game, not measurements
+
+struct MarketState {
+    name: &'static str,
+    reliability: f64,
+    price_shift: f64,
+}
+
+// the market is offline in these states, so the station is never a destination
+const CLOSED_STATES: [&'static str; 2] = ["damaged", "abandoned"];
+
+const MARKET_STATES: [MarketState; 13] = [
+    MarketState {
+        name: "lockdown",
+        reliability: 0.6,
+        price_shift: 0.0,
+    },
+    MarketState {
+        name: "underattack",
+        reliability: 0.5,
+        price_shift: 0.0,
+    },
+    MarketState {
+        name: "underrepairs",
+        reliability: 0.7,
+        price_shift: -0.1,
+    },
+    MarketState {
+        name: "infrastructurefailure",
+        reliability: 0.7,
+        price_shift: -0.1,
+    },
+    MarketState {
+        name: "famine",
+        reliability: 0.75,
+        price_shift: 0.0,
+    },
+    MarketState {
+        name: "outbreak",
+        reliability: 0.8,
+        price_shift: 0.0,
+    },
+    MarketState {
+        name: "naturaldisaster",
+        reliability: 0.8,
+        price_shift: -0.05,
+    },
+    MarketState {
+        name: "civilwar",
+        reliability: 0.8,
+        price_shift: -0.05,
+    },
+    MarketState {
+        name: "war",
+        reliability: 0.85,
+        price_shift: -0.05,
+    },
+    MarketState {
+        name: "pirateattack",
+        reliability: 0.85,
+        price_shift: 0.0,
+    },
+    MarketState {
+        name: "terroristattack",
+        reliability: 0.85,
+        price_shift: 0.0,
+    },
+    MarketState {
+        name: "civilunrest",
+        reliability: 0.9,
+        price_shift: 0.0,
+    },
+    MarketState {
+        name: "bust",
+        reliability: 0.95,
+        price_shift: -0.1,
+    },
+];
+
+fn normalize(state: &String) -> String {
+    state
+        .chars()
+        .filter(|c| !c.is_whitespace())
+        .collect::<String>()
+        .to_lowercase()
+}
+
+fn states(system: &System, station: &Station) -> Vec<String> {
+    station
+        .states
+        .iter()
+        .chain(system.states.iter())
+        .map(normalize)
+        .collect()
+}
+
+pub fn is_closed(system: &System, station: &Station) -> bool {
+    states(system, station)
+        .iter()
+        .any(|e| CLOSED_STATES.contains(&&e[..]))
+}
+
+// the combined (reliability, price_shift) of every state at the station.
+// (1.0, 0.0) when there is nothing to worry about
+pub fn outlook(system: &System, station: &Station) -> (f64, f64) {
+    let mut reliability = 1f64;
+    let mut price_shift = 0f64;
+
+    if is_closed(system, station) {
+        return (0f64, 0f64);
+    }
+
+    let mut seen = Vec::new();
+    for state in states(system, station) {
+        // the station and system often report the same state
+        if seen.contains(&state) {
+            continue;
+        }
+
+        if let Some(model) = MARKET_STATES.iter().find(|e| e.name == &state[..]) {
+            reliability *= model.reliability;
+            price_shift += model.price_shift;
+        }
+
+        seen.push(state);
+    }
+
+    (reliability, price_shift)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::filter::FilterOptions;
+    use crate::data::source::FixtureSource;
+    use crate::data::trader::ShipSize;
+    use crate::data::Universe;
+
+    #[test]
+    fn test_market_outlook() {
+        let universe =
+            Universe::from_source(&FixtureSource::sample(), &FilterOptions::new(ShipSize::Large));
+
+        let chono = universe.get_system(2).unwrap();
+        let siddha = universe.get_station(20).unwrap();
+        assert_eq!((1f64, 0f64), outlook(chono, siddha));
+
+        let mut chono = chono.clone();
+        let mut siddha = siddha.clone();
+        chono.states = vec!["Bust".to_string(), "Civil War".to_string()];
+        siddha.states = vec!["CivilWar".to_string()];
+        let (reliability, price_shift) = outlook(&chono, &siddha);
+        assert!((reliability - 0.76).abs() < 1e-9);
+        assert!((price_shift + 0.15).abs() < 1e-9);
+        assert!(!is_closed(&chono, &siddha));
+
+        siddha.states = vec!["Damaged".to_string()];
+        assert!(is_closed(&chono, &siddha));
+        assert_eq!((0f64, 0f64), outlook(&chono, &siddha));
+    }
+}
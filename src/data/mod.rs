@@ -1,11 +1,25 @@
 mod adjustments;
+mod cache;
 mod universe_index;
 
 pub mod eddb;
+pub mod eddn;
+pub mod exclusions;
+pub mod filter;
+pub mod housekeeping;
+pub mod legality;
+pub mod market_state;
+pub mod permits;
+pub mod source;
+pub mod spansh;
 pub mod trader;
 
-mod edce;
-pub use self::edce::EdceData;
+pub mod journal;
+pub use self::journal::JournalData;
+
+mod journal_watch;
+pub use self::journal_watch::JournalWatcher;
+pub use self::journal_watch::WatchEvent;
 
 mod universe;
 pub use self::universe::Universe;
@@ -0,0 +1,35 @@
+// populated systems which need a permit to enter.
+// eddb dumps flag these themselves, but spansh dumps don't, so this list fills the gap.
+// it only covers the well known locks - if the game adds one, exclude the system in
+// exclusions.json until the list catches up
+
+const PERMIT_SYSTEMS: [&'static str; 22] = [
+    "Achenar",
+    "Alioth",
+    "Beta Hydri",
+    "Crom",
+    "Exbeur",
+    "Facece",
+    "Hodack",
+    "Hors",
+    "Isinor",
+    "Jotun",
+    "LFT 509",
+    "Luyten 347-14",
+    "Mbooni",
+    "Peregrina",
+    "PLX 695",
+    "Shinrarta Dezhra",
+    "Sirius",
+    "Sol",
+    "Summerland",
+    "Terra Mater",
+    "Van Maanen's Star",
+    "Vega",
+];
+
+pub fn is_permit_locked(system_name: &str) -> bool {
+    PERMIT_SYSTEMS
+        .iter()
+        .any(|e| e.eq_ignore_ascii_case(system_name.trim()))
+}
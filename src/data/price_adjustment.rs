@@ -55,11 +55,14 @@ impl PriceAdjustment {
         }
     }
 
-    pub fn load_all() -> Vec<PriceAdjustment> {
-        load_adjustments("price")
+    // true if the listing was collected after this adjustment was made, so the adjustment no longer applies.
+    // the adjustment has to be 10 minutes before the EDDB entry,
+    // in case we created the update using the Player Journal
+    pub fn is_superseded_by(&self, listing: &Listing) -> bool {
+        listing.collected_at >= self.timestamp.saturating_sub(600)
     }
 
     pub fn save(&self) {
-        save_adjustment("price", self);
+        save_adjustment(AdjustmentRecord::Price(self.clone()));
     }
 }
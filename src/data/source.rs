@@ -0,0 +1,352 @@
+use std::cell::RefCell;
+use std::path::Path;
+use std::path::PathBuf;
+
+use crate::data::eddb::*;
+use crate::data::spansh;
+use crate::data::spansh::CommodityIds;
+use crate::data::spansh::SpanshSystem;
+use crate::persist::*;
+
+// a place the universe can be built from.
+// the eddb record types are the common format - other dumps are converted into them.
+pub trait MarketDataSource {
+    // a human readable description, for progress output
+    fn describe(&self) -> String;
+
+    fn commodities(&self) -> Vec<CommodityJson>;
+    fn systems(&self) -> Vec<SystemJson>;
+    fn stations(&self) -> Vec<StationJson>;
+    fn listings(&self) -> Vec<StationCommodityListingJson>;
+
+    fn load(&self) -> EddbDump {
+        println!("Loading commodities...");
+        let commodities = self.commodities();
+
+        println!("Loading systems...");
+        let systems = self.systems();
+
+        println!("Loading stations...");
+        let stations = self.stations();
+
+        println!("Loading listings...");
+        let listings = self.listings();
+
+        EddbDump {
+            commodities: commodities,
+            systems: systems,
+            stations: stations,
+            listings: listings,
+        }
+    }
+}
+
+// picks a source for the configured location:
+//  http(s) urls are eddb-style mirrors, directories hold eddb-style dumps,
+//  and anything else is a spansh galaxy dump
+pub fn from_config(source: &String) -> Box<dyn MarketDataSource> {
+    let lowercase = source.to_lowercase();
+
+    if lowercase.starts_with("http://") || lowercase.starts_with("https://") {
+        return Box::new(HttpMirrorSource::new(source));
+    }
+
+    let path = Path::new(source);
+    if path.is_dir() {
+        Box::new(LocalDirectorySource::new(path))
+    } else {
+        Box::new(SpanshFileSource::new(path))
+    }
+}
+
+// a web server which hosts the eddb v6 archive layout
+pub struct HttpMirrorSource {
+    base_url: String,
+}
+
+impl HttpMirrorSource {
+    pub fn new(base_url: &String) -> HttpMirrorSource {
+        HttpMirrorSource {
+            base_url: base_url.trim_end_matches('/').to_string(),
+        }
+    }
+
+    fn url(&self, file: &str) -> String {
+        format!("{}/{}", self.base_url, file)
+    }
+}
+
+impl MarketDataSource for HttpMirrorSource {
+    fn describe(&self) -> String {
+        self.base_url.clone()
+    }
+
+    fn commodities(&self) -> Vec<CommodityJson> {
+        http_read_json(&self.url("commodities.json"))
+    }
+
+    fn systems(&self) -> Vec<SystemJson> {
+        http_read_json_array(&self.url("systems_populated.json"))
+    }
+
+    fn stations(&self) -> Vec<StationJson> {
+        http_read_json_array(&self.url("stations.json"))
+    }
+
+    fn listings(&self) -> Vec<StationCommodityListingJson> {
+        http_read_csv(&self.url("listings.csv"))
+    }
+}
+
+// a directory with the eddb dump files
+pub struct LocalDirectorySource {
+    dir: PathBuf,
+}
+
+impl LocalDirectorySource {
+    pub fn new(dir: &Path) -> LocalDirectorySource {
+        LocalDirectorySource {
+            dir: dir.to_path_buf(),
+        }
+    }
+}
+
+impl MarketDataSource for LocalDirectorySource {
+    fn describe(&self) -> String {
+        self.dir.to_str().unwrap_or("<unknown>").to_string()
+    }
+
+    fn commodities(&self) -> Vec<CommodityJson> {
+        read_json(&self.dir.join("commodities.json"))
+    }
+
+    fn systems(&self) -> Vec<SystemJson> {
+        // the full systems.json works too, but it's much larger
+        let populated = self.dir.join("systems_populated.json");
+        match populated.exists() {
+            true => read_json_array(&populated),
+            false => read_json_array(&self.dir.join("systems.json")),
+        }
+    }
+
+    fn stations(&self) -> Vec<StationJson> {
+        read_json_array(&self.dir.join("stations.json"))
+    }
+
+    fn listings(&self) -> Vec<StationCommodityListingJson> {
+        read_csv(&self.dir.join("listings.csv"))
+    }
+}
+
+// a spansh galaxy_populated.json file.
+// everything lives in one file, which can be several GB, so it is only parsed once.
+// the converted dump is kept until it's handed out - each part can only be taken once
+pub struct SpanshFileSource {
+    path: PathBuf,
+    dump: RefCell<Option<EddbDump>>,
+}
+
+impl SpanshFileSource {
+    pub fn new(path: &Path) -> SpanshFileSource {
+        SpanshFileSource {
+            path: path.to_path_buf(),
+            dump: RefCell::new(None),
+        }
+    }
+
+    fn take<T, F>(&self, part: F) -> Vec<T>
+    where
+        F: FnOnce(&mut EddbDump) -> &mut Vec<T>,
+    {
+        let mut dump = self.dump.borrow_mut();
+        if dump.is_none() {
+            *dump = Some(self.convert());
+        }
+
+        std::mem::replace(part(dump.as_mut().unwrap()), Vec::new())
+    }
+
+    fn convert(&self) -> EddbDump {
+        println!("Loading {}...", self.describe());
+        let spansh_systems: Vec<SpanshSystem> = read_json_array(&self.path);
+
+        let mut ids = CommodityIds::load();
+        let known = ids.len();
+        let dump = spansh::convert_systems(spansh_systems, &mut ids);
+        if ids.len() != known {
+            ids.save();
+        }
+
+        dump
+    }
+}
+
+impl MarketDataSource for SpanshFileSource {
+    fn describe(&self) -> String {
+        self.path.to_str().unwrap_or("<unknown>").to_string()
+    }
+
+    fn commodities(&self) -> Vec<CommodityJson> {
+        self.take(|dump| &mut dump.commodities)
+    }
+
+    fn systems(&self) -> Vec<SystemJson> {
+        self.take(|dump| &mut dump.systems)
+    }
+
+    fn stations(&self) -> Vec<StationJson> {
+        self.take(|dump| &mut dump.stations)
+    }
+
+    fn listings(&self) -> Vec<StationCommodityListingJson> {
+        self.take(|dump| &mut dump.listings)
+    }
+
+    fn load(&self) -> EddbDump {
+        match self.dump.borrow_mut().take() {
+            Some(dump) => dump,
+            None => self.convert(),
+        }
+    }
+}
+
+// records held in memory.  used by tests, so a universe can be built without files or the network
+#[allow(dead_code)]
+#[derive(Default, Clone)]
+pub struct FixtureSource {
+    pub commodities: Vec<CommodityJson>,
+    pub systems: Vec<SystemJson>,
+    pub stations: Vec<StationJson>,
+    pub listings: Vec<StationCommodityListingJson>,
+}
+
+impl MarketDataSource for FixtureSource {
+    fn describe(&self) -> String {
+        "fixture".to_string()
+    }
+
+    fn commodities(&self) -> Vec<CommodityJson> {
+        self.commodities.clone()
+    }
+
+    fn systems(&self) -> Vec<SystemJson> {
+        self.systems.clone()
+    }
+
+    fn stations(&self) -> Vec<StationJson> {
+        self.stations.clone()
+    }
+
+    fn listings(&self) -> Vec<StationCommodityListingJson> {
+        self.listings.clone()
+    }
+}
+
+#[cfg(test)]
+impl FixtureSource {
+    // three systems on a line: a buy station, a sell station with a small outpost next door,
+    // and a permit-locked system at the end
+    pub fn sample() -> FixtureSource {
+        let commodity = |id: u16, name: &str, category: &str| CommodityJson {
+            id: id,
+            name: name.to_string(),
+            category_id: 0,
+            average_price: None,
+            category: CommodityCategoryJson {
+                name: category.to_string(),
+            },
+        };
+
+        let system = |id: u32, name: &str, x: f64, needs_permit: bool| SystemJson {
+            id: id,
+            name: name.to_string(),
+            x: x,
+            y: 0f64,
+            z: 0f64,
+            needs_permit: Some(needs_permit),
+            updated_at: 0,
+            controlling_minor_faction: None,
+            government: None,
+            allegiance: None,
+            population: Some(1000000),
+            states: Vec::new(),
+            security: Some("High".to_string()),
+            primary_economy: Some("Industrial".to_string()),
+        };
+
+        let station = |id: u32, system_id: u32, name: &str, pad: &str, prohibited: Vec<&str>| {
+            StationJson {
+                id: id,
+                system_id: system_id,
+                name: name.to_string(),
+                max_landing_pad_size: Some(pad.to_string()),
+                distance_to_star: Some(100),
+                prohibited_commodities: prohibited.iter().map(|e| e.to_string()).collect(),
+                controlling_minor_faction: None,
+                government: Some("Democracy".to_string()),
+                allegiance: None,
+                states: Vec::new(),
+                has_blackmarket: Some(false),
+                has_refuel: Some(true),
+                has_repair: Some(true),
+                has_rearm: Some(true),
+                has_outfitting: Some(false),
+                has_shipyard: Some(false),
+                economies: vec!["Industrial".to_string()],
+                updated_at: 0,
+                market_updated_at: None,
+                is_planetary: Some(false),
+                // Giger Hub gets the market id from the journal fixture
+                market_id: Some(3223343606 + id as u64),
+            }
+        };
+
+        let listing = |id: u32, station_id: u32, commodity_id: u16, buy: i32, sell: i32| {
+            StationCommodityListingJson {
+                id: id,
+                station_id: station_id,
+                commodity_id: commodity_id,
+                supply: match buy > 0 {
+                    true => 10000,
+                    false => 0,
+                },
+                buy_price: buy,
+                sell_price: sell,
+                collected_at: 0,
+                demand: 10000,
+            }
+        };
+
+        // a surface port, only included with --planetary
+        let mut surface_port = station(11, 1, "Surface Port", "L", vec![]);
+        surface_port.is_planetary = Some(true);
+
+        FixtureSource {
+            commodities: vec![
+                commodity(1, "Gold", "Metals"),
+                commodity(2, "Explosives", "Chemicals"),
+            ],
+            systems: vec![
+                system(1, "Peraesii", 0f64, false),
+                system(2, "Chono", 10f64, false),
+                system(3, "Maiki", 20f64, true),
+            ],
+            stations: vec![
+                station(10, 1, "Giger Hub", "L", vec![]),
+                surface_port,
+                station(20, 2, "Siddha Ring", "L", vec!["Explosives"]),
+                station(21, 2, "Small Outpost", "S", vec![]),
+                station(30, 3, "Permit Port", "L", vec![]),
+            ],
+            listings: vec![
+                listing(1, 10, 1, 9000, 8800),
+                listing(2, 10, 2, 200, 180),
+                listing(3, 20, 1, 0, 10000),
+                listing(4, 20, 2, 0, 400),
+                listing(5, 21, 1, 0, 11000),
+                listing(6, 30, 1, 0, 12000),
+                listing(7, 11, 1, 0, 10500),
+            ],
+        }
+    }
+}
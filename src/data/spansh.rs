@@ -0,0 +1,540 @@
+use std::collections::BTreeMap;
+use std::collections::HashMap;
+use std::collections::HashSet;
+use std::path::PathBuf;
+
+use serde::Deserialize;
+use serde::Serialize;
+
+use crate::data::eddb::*;
+use crate::persist::*;
+use crate::util::timestamp::parse_utc_timestamp;
+
+// the spansh galaxy_populated.json dump nests stations and markets inside each system.
+// we flatten it into the eddb structures, so the rest of the import is shared.
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct SpanshSystem {
+    pub id64: u64,
+    pub name: String,
+    pub coords: SpanshCoords,
+    pub date: Option<String>,
+    pub allegiance: Option<String>,
+    pub government: Option<String>,
+    pub controlling_faction: Option<SpanshFaction>,
+    #[serde(default)]
+    pub factions: Vec<SpanshFactionPresence>,
+    pub population: Option<u64>,
+    pub security: Option<String>,
+    pub primary_economy: Option<String>,
+    #[serde(default)]
+    pub stations: Vec<SpanshStation>,
+    #[serde(default)]
+    pub bodies: Vec<SpanshBody>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct SpanshCoords {
+    pub x: f64,
+    pub y: f64,
+    pub z: f64,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct SpanshFaction {
+    pub name: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct SpanshFactionPresence {
+    pub name: String,
+    pub state: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct SpanshBody {
+    #[serde(default)]
+    pub stations: Vec<SpanshStation>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct SpanshStation {
+    pub id: u64,
+    pub name: String,
+    #[serde(rename = "type")]
+    pub station_type: Option<String>,
+    pub government: Option<String>,
+    pub allegiance: Option<String>,
+    pub controlling_faction: Option<String>,
+    pub controlling_faction_state: Option<String>,
+    // missing from some dumps.  the services are unknown, rather than none
+    #[serde(default)]
+    pub services: Option<Vec<String>>,
+    // economy name to share, e.g. { "Refinery": 100.0 }
+    #[serde(default)]
+    pub economies: HashMap<String, f64>,
+    pub distance_to_arrival: Option<f64>,
+    pub update_time: Option<String>,
+    pub landing_pads: Option<SpanshLandingPads>,
+    pub market: Option<SpanshMarket>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct SpanshLandingPads {
+    #[serde(default)]
+    pub large: u32,
+    #[serde(default)]
+    pub medium: u32,
+    #[serde(default)]
+    pub small: u32,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct SpanshMarket {
+    #[serde(default)]
+    pub commodities: Vec<SpanshCommodity>,
+    #[serde(default)]
+    pub prohibited_commodities: Vec<String>,
+    pub update_time: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct SpanshCommodity {
+    pub name: String,
+    pub category: String,
+    pub commodity_id: u64,
+    pub demand: i64,
+    pub supply: i64,
+    pub buy_price: i32,
+    pub sell_price: i32,
+}
+
+impl SpanshStation {
+    fn is_planetary(&self) -> bool {
+        match self.station_type {
+            Some(ref t) => t.contains("Planetary") || t.contains("Settlement"),
+            None => false,
+        }
+    }
+
+    fn is_fleet_carrier(&self) -> bool {
+        match self.station_type {
+            Some(ref t) => t.contains("Carrier"),
+            None => false,
+        }
+    }
+
+    fn has_service(&self, service: &str) -> Option<bool> {
+        self.services
+            .as_ref()
+            .map(|services| services.iter().any(|e| e == service))
+    }
+
+    fn has_blackmarket(&self) -> Option<bool> {
+        self.has_service("Black Market")
+    }
+
+    // the largest share first
+    fn economies(&self) -> Vec<String> {
+        let mut economies: Vec<(&String, &f64)> = self.economies.iter().collect();
+        economies.sort_by(|a, b| b.1.partial_cmp(a.1).unwrap_or(std::cmp::Ordering::Equal));
+        economies.into_iter().map(|(name, _)| name.clone()).collect()
+    }
+
+    fn max_landing_pad_size(&self) -> String {
+        match self.landing_pads {
+            Some(ref pads) if pads.large > 0 => "L",
+            Some(ref pads) if pads.medium > 0 => "M",
+            Some(ref pads) if pads.small > 0 => "S",
+            _ => "None",
+        }
+        .to_string()
+    }
+}
+
+// spansh ids don't fit in the eddb id types.
+// station and system ids are folded into 32 bits, which keeps them stable between dumps,
+// so saved adjustments still apply after a refresh.
+// two ids can fold to the same value - convert_systems keeps the first, and skips the other
+fn fold_id(id: u64) -> u32 {
+    if id <= u32::max_value() as u64 {
+        id as u32
+    } else {
+        ((id >> 32) ^ (id & 0xFFFF_FFFF)) as u32
+    }
+}
+
+// the game's commodity ids don't fit in our u16 ids, so we hand out our own, and remember them
+// in ~/.elite_trader/spansh_commodities.json.  adjustments are saved by commodity id,
+// so an id has to mean the same commodity in every dump, whatever else the dump lists
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct CommodityIds {
+    ids: BTreeMap<u64, u16>,
+}
+
+impl CommodityIds {
+    pub fn get_path() -> PathBuf {
+        get_base_directory().join("spansh_commodities.json")
+    }
+
+    pub fn load() -> CommodityIds {
+        let path = Self::get_path();
+
+        if path.exists() {
+            read_json(&path)
+        } else {
+            CommodityIds::default()
+        }
+    }
+
+    pub fn save(&self) {
+        write_json(&Self::get_path(), self);
+    }
+
+    pub fn len(&self) -> usize {
+        self.ids.len()
+    }
+
+    // the id for the game's commodity id.  commodities we haven't seen get the next free id
+    pub fn assign(&mut self, game_id: u64) -> u16 {
+        if let Some(id) = self.ids.get(&game_id) {
+            return *id;
+        }
+
+        let id = self.ids.values().max().map_or(1, |max| max + 1);
+        self.ids.insert(game_id, id);
+        id
+    }
+}
+
+pub fn convert_systems(spansh_systems: Vec<SpanshSystem>, ids: &mut CommodityIds) -> EddbDump {
+    // new commodities are numbered in order of the game's commodity id,
+    // so the first dump gets the same ids on every machine
+    let mut game_commodities = BTreeMap::new();
+    for system in &spansh_systems {
+        let stations = system
+            .stations
+            .iter()
+            .chain(system.bodies.iter().flat_map(|b| b.stations.iter()));
+        for station in stations {
+            if let Some(ref market) = station.market {
+                for commodity in &market.commodities {
+                    game_commodities
+                        .entry(commodity.commodity_id)
+                        .or_insert((commodity.name.clone(), commodity.category.clone()));
+                }
+            }
+        }
+    }
+
+    let mut commodity_ids = HashMap::new();
+    let mut commodities = Vec::with_capacity(game_commodities.len());
+    for (game_id, (name, category)) in game_commodities {
+        let id = ids.assign(game_id);
+        commodity_ids.insert(game_id, id);
+        commodities.push(CommodityJson {
+            id: id,
+            name: name,
+            category_id: 0,
+            // filled in from the listings when the universe is built
+            average_price: None,
+            category: CommodityCategoryJson { name: category },
+        });
+    }
+
+    let mut systems = Vec::with_capacity(spansh_systems.len());
+    let mut stations = Vec::new();
+    let mut listings = Vec::new();
+
+    // folded ids which are already taken, and the systems and stations skipped because of it
+    let mut system_ids = HashSet::new();
+    let mut station_ids = HashSet::new();
+    let mut skipped_systems = 0;
+    let mut skipped_stations = 0;
+
+    for system in spansh_systems {
+        let system_id = fold_id(system.id64);
+        if !system_ids.insert(system_id) {
+            skipped_systems += 1;
+            continue;
+        }
+
+        let controlling_faction = system.controlling_faction.map(|f| f.name);
+
+        // spansh gives each faction a single state.  the system has the controlling faction's
+        let system_states = system
+            .factions
+            .iter()
+            .filter(|f| Some(&f.name) == controlling_faction.as_ref())
+            .filter_map(|f| f.state.clone())
+            .filter(|s| s != "None")
+            .map(|s| StateJson { name: s })
+            .collect();
+
+        let system_updated_at = system
+            .date
+            .as_ref()
+            .and_then(|d| parse_utc_timestamp(d))
+            .unwrap_or(0) as u64;
+
+        let system_stations = system
+            .stations
+            .into_iter()
+            .chain(system.bodies.into_iter().flat_map(|b| b.stations.into_iter()));
+
+        for station in system_stations {
+            let station_id = fold_id(station.id);
+            if !station_ids.insert(station_id) {
+                skipped_stations += 1;
+                continue;
+            }
+
+            let updated_at = station
+                .update_time
+                .as_ref()
+                .and_then(|d| parse_utc_timestamp(d))
+                .unwrap_or(0) as u64;
+
+            let market_updated_at = station
+                .market
+                .as_ref()
+                .and_then(|m| m.update_time.as_ref())
+                .and_then(|d| parse_utc_timestamp(d))
+                .map(|t| t as u64);
+
+            // carriers are identified by their government in eddb
+            let government = match station.is_fleet_carrier() {
+                true => Some("Private Ownership".to_string()),
+                false => station.government.clone(),
+            };
+
+            stations.push(StationJson {
+                id: station_id,
+                system_id: system_id,
+                name: station.name.clone(),
+                max_landing_pad_size: Some(station.max_landing_pad_size()),
+                distance_to_star: station.distance_to_arrival.map(|d| d as u32),
+                prohibited_commodities: station
+                    .market
+                    .as_ref()
+                    .map(|m| m.prohibited_commodities.clone())
+                    .unwrap_or(Vec::new()),
+                controlling_minor_faction: station.controlling_faction.clone(),
+                government: government,
+                allegiance: station.allegiance.clone(),
+                states: station
+                    .controlling_faction_state
+                    .iter()
+                    .filter(|s| *s != "None")
+                    .map(|s| StateJson { name: s.clone() })
+                    .collect(),
+                has_blackmarket: station.has_blackmarket(),
+                has_refuel: station.has_service("Refuel"),
+                has_repair: station.has_service("Repair"),
+                has_rearm: station.has_service("Restock"),
+                has_outfitting: station.has_service("Outfitting"),
+                has_shipyard: station.has_service("Shipyard"),
+                economies: station.economies(),
+                updated_at: updated_at,
+                market_updated_at: market_updated_at,
+                is_planetary: Some(station.is_planetary()),
+                // spansh station ids are the game's market ids
+                market_id: Some(station.id),
+            });
+
+            if let Some(market) = station.market {
+                for commodity in market.commodities {
+                    listings.push(StationCommodityListingJson {
+                        id: listings.len() as u32,
+                        station_id: station_id,
+                        commodity_id: commodity_ids[&commodity.commodity_id],
+                        supply: commodity.supply,
+                        buy_price: commodity.buy_price,
+                        sell_price: commodity.sell_price,
+                        collected_at: market_updated_at.unwrap_or(updated_at),
+                        demand: commodity.demand,
+                    });
+                }
+            }
+        }
+
+        systems.push(SystemJson {
+            id: system_id,
+            name: system.name,
+            x: system.coords.x,
+            y: system.coords.y,
+            z: system.coords.z,
+            // spansh doesn't publish permit requirements, so the built-in list is used
+            needs_permit: None,
+            updated_at: system_updated_at,
+            controlling_minor_faction: controlling_faction,
+            government: system.government,
+            allegiance: system.allegiance,
+            population: system.population,
+            states: system_states,
+            security: system.security,
+            primary_economy: system.primary_economy,
+        });
+    }
+
+    if skipped_systems + skipped_stations > 0 {
+        println!(
+            "Skipped {} systems and {} stations whose spansh ids collide with another",
+            skipped_systems, skipped_stations
+        );
+    }
+
+    EddbDump {
+        commodities: commodities,
+        systems: systems,
+        stations: stations,
+        listings: listings,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::filter::FilterOptions;
+    use crate::data::source::FixtureSource;
+    use crate::data::trader::ShipSize;
+    use crate::data::Universe;
+    use std::path::Path;
+
+    fn fixture() -> Vec<SpanshSystem> {
+        let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("src/dummy/spansh");
+        read_json_array(&dir.join("galaxy_populated.json"))
+    }
+
+    fn commodity_id(dump: &EddbDump, name: &str) -> u16 {
+        dump.commodities.iter().find(|e| e.name == name).unwrap().id
+    }
+
+    #[test]
+    fn test_convert_systems() {
+        let dump = convert_systems(fixture(), &mut CommodityIds::default());
+
+        // numbered in order of the game's ids - water is 128049166, and gold 128049202
+        assert_eq!(1, commodity_id(&dump, "Water"));
+        assert_eq!(2, commodity_id(&dump, "Gold"));
+
+        assert_eq!(2, dump.systems.len());
+        let chono = &dump.systems[1];
+        assert_eq!(fold_id(2869708727769), chono.id);
+        assert_eq!("Lockdown", chono.states[0].name);
+        assert_eq!(None, chono.needs_permit);
+
+        // stations on bodies are flattened into their system
+        assert_eq!(2, dump.stations.len());
+        let lincoln = &dump.stations[0];
+        assert_eq!(Some("L".to_string()), lincoln.max_landing_pad_size);
+        assert_eq!(vec!["Service", "Refinery"], lincoln.economies);
+        assert_eq!(vec!["Narcotics"], lincoln.prohibited_commodities);
+        assert_eq!(Some(true), lincoln.has_refuel);
+
+        let base = &dump.stations[1];
+        assert_eq!(chono.id, base.system_id);
+        assert_eq!(Some(3228191744), base.market_id);
+        assert_eq!(Some("M".to_string()), base.max_landing_pad_size);
+        assert_eq!(Some(true), base.is_planetary);
+        assert_eq!(Some(true), base.has_blackmarket);
+        assert_eq!(Some(false), base.has_refuel);
+
+        let gold = dump.listings.iter().find(|e| e.station_id == base.id).unwrap();
+        assert_eq!(2, gold.commodity_id);
+        assert_eq!(900, gold.supply);
+        assert_eq!(Some(gold.collected_at), base.market_updated_at);
+    }
+
+    #[test]
+    fn test_convert_without_services() {
+        let mut systems = fixture();
+        systems[0].stations[0].services = None;
+
+        let dump = convert_systems(systems, &mut CommodityIds::default());
+        let lincoln = &dump.stations[0];
+        assert_eq!(None, lincoln.has_refuel);
+        assert_eq!(None, lincoln.has_blackmarket);
+    }
+
+    #[test]
+    fn test_colliding_ids_are_skipped() {
+        let mut systems = fixture();
+
+        // the halves of Chono's id64 swapped, which fold to the same id
+        let mut copy = systems[1].clone();
+        let id64 = copy.id64;
+        copy.id64 = (id64 << 32) | (id64 >> 32);
+        copy.name = "Chono Copy".to_string();
+        assert_ne!(systems[1].id64, copy.id64);
+        assert_eq!(fold_id(systems[1].id64), fold_id(copy.id64));
+        systems.push(copy);
+
+        // and a second station with Abraham Lincoln's id
+        let station = systems[0].stations[0].clone();
+        systems[0].stations.push(station);
+
+        let dump = convert_systems(systems, &mut CommodityIds::default());
+        assert_eq!(2, dump.systems.len());
+        assert_eq!("Chono", dump.systems[1].name);
+        assert_eq!(2, dump.stations.len());
+
+        let ids: HashSet<u32> = dump.stations.iter().map(|e| e.id).collect();
+        assert_eq!(2, ids.len());
+        let listing_ids: HashSet<u32> = dump.listings.iter().map(|e| e.station_id).collect();
+        assert_eq!(ids, listing_ids);
+    }
+
+    #[test]
+    fn test_commodity_ids_are_stable() {
+        let mut ids = CommodityIds::default();
+        convert_systems(fixture(), &mut ids);
+
+        // a later dump lists a commodity with a lower game id than the others
+        let mut systems = fixture();
+        let market = systems[0].stations[0].market.as_mut().unwrap();
+        market.commodities.push(SpanshCommodity {
+            name: "Hydrogen Fuel".to_string(),
+            category: "Chemicals".to_string(),
+            commodity_id: 128049100,
+            demand: 0,
+            supply: 100,
+            buy_price: 110,
+            sell_price: 100,
+        });
+
+        let dump = convert_systems(systems, &mut ids);
+        assert_eq!(1, commodity_id(&dump, "Water"));
+        assert_eq!(2, commodity_id(&dump, "Gold"));
+        assert_eq!(3, commodity_id(&dump, "Hydrogen Fuel"));
+
+        // and the ids are the same after a restart
+        let json = serde_json::to_string(&ids).unwrap();
+        let mut ids: CommodityIds = serde_json::from_str(&json).unwrap();
+        assert_eq!(2, ids.assign(128049202));
+        assert_eq!(4, ids.assign(128049000));
+    }
+
+    #[test]
+    fn test_spansh_permits() {
+        let dump = convert_systems(fixture(), &mut CommodityIds::default());
+        let source = FixtureSource {
+            commodities: dump.commodities,
+            systems: dump.systems,
+            stations: dump.stations,
+            listings: dump.listings,
+        };
+        let universe = Universe::from_source(&source, &FilterOptions::new(ShipSize::Small));
+
+        // spansh doesn't flag Sol, but it needs a permit
+        let sol = universe.get_system_by_name(&"Sol".to_string()).unwrap();
+        assert!(sol.needs_permit);
+        let chono = universe.get_system_by_name(&"Chono".to_string()).unwrap();
+        assert!(!chono.needs_permit);
+    }
+}
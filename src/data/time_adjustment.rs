@@ -1,5 +1,6 @@
 use crate::data::adjustments::*;
 use crate::search::time_estimate::TimeEstimate;
+use crate::search::time_estimate::BUY_TIME;
 use crate::search::FullTrade;
 use serde::Deserialize;
 use serde::Serialize;
@@ -12,6 +13,9 @@ use time::OffsetDateTime;
 // if a recorded adjustment exists (for local star -> station time),
 //  use it as the system estimate
 
+// only the most recent trips are used to adjust estimates
+pub const TIME_ADJUSTMENT_HISTORY: usize = 20;
+
 #[derive(Serialize, Deserialize, Clone)]
 pub struct TimeAdjustment {
     pub buy_system_id: u32,
@@ -36,9 +40,20 @@ impl TimeAdjustment {
             return None;
         }
 
+        Some(Self::new_measured(trade, num_seconds))
+    }
+
+    // creates an adjustment from a trip time which was measured by journal event timestamps.
+    // unlike keypresses, these are exact, so we don't need to guess whether the player was late
+    // the journal trip starts at the MarketBuy event, so we add our estimate of the buy time
+    pub fn from_journal(trade: &FullTrade, num_seconds: f64) -> TimeAdjustment {
+        Self::new_measured(trade, num_seconds + BUY_TIME)
+    }
+
+    fn new_measured(trade: &FullTrade, num_seconds: f64) -> TimeAdjustment {
         let timestamp =
             OffsetDateTime::now().timestamp() - OffsetDateTime::unix_epoch().timestamp();
-        Some(TimeAdjustment {
+        TimeAdjustment {
             buy_system_id: trade.unit.buy_system.system_id,
             buy_station_id: trade.unit.buy_station.station_id,
 
@@ -52,14 +67,10 @@ impl TimeAdjustment {
             // I'm pretty sure the current timestamp isn't going to be before Jan 1st 1970...
             // lets cast it to u64
             timestamp: timestamp as u64,
-        })
-    }
-
-    pub fn load_all() -> Vec<TimeAdjustment> {
-        load_adjustments("time")
+        }
     }
 
     pub fn save(&self) {
-        save_adjustment("time", self);
+        save_adjustment(AdjustmentRecord::Time(self.clone()));
     }
 }
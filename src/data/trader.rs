@@ -1,7 +1,11 @@
 use serde::Deserialize;
 use serde::Serialize;
 use spatial::octree::Index;
+use std::fmt::Display;
+use std::fmt::Error;
+use std::fmt::Formatter;
 use std::str::FromStr;
+use std::sync::Arc;
 
 pub trait Identified<K> {
     fn to_id(&self) -> K;
@@ -15,14 +19,24 @@ pub struct System {
     pub y: f64,
     pub z: f64,
     pub needs_permit: bool,
-    pub stations: Vec<Station>,
-    pub updated_at: u64, //	faction: Option<String>,
-                         //	population: Option<u64>,
-                         //	government: Option<String>,
-                         //	allegiance: Option<String>,
-                         //	state: Option<String>,
-                         //	security: Option<String>,
-                         //	primary_economy: Option<String>
+    // shared between universe snapshots, until one of them changes the station
+    pub stations: Vec<Arc<Station>>,
+    pub updated_at: u64,
+    #[serde(default)]
+    pub controlling_faction: Option<String>,
+    #[serde(default)]
+    pub government: Option<String>,
+    #[serde(default)]
+    pub allegiance: Option<String>,
+    #[serde(default)]
+    pub population: Option<u64>,
+    // the controlling faction's states, e.g. Boom, Lockdown
+    #[serde(default)]
+    pub states: Vec<String>,
+    #[serde(default)]
+    pub security: Option<String>,
+    #[serde(default)]
+    pub primary_economy: Option<String>,
 }
 
 impl Index<f64> for System {
@@ -47,9 +61,9 @@ impl System {
     pub fn get_station_by_name(&self, name: String) -> Option<&Station> {
         let name_lowercase = name.to_lowercase();
 
-        for station in &*self.stations {
+        for station in &self.stations {
             if station.station_name.to_lowercase() == name_lowercase {
-                return Some(station);
+                return Some(&**station);
             }
         }
 
@@ -71,20 +85,75 @@ pub struct Station {
     pub listings: Vec<Listing>,
     pub prohibited_commodities: Vec<u16>,
     pub updated_at: u64,
-    //	faction: Option<String>,
-    //	government: Option<String>,
-    //	allegiance: Option<String>,
-    //	state: Option<String>,
-    //	has_blackmarket: Option<u8>,
+    #[serde(default)]
+    pub faction: Option<String>,
+    #[serde(default)]
+    pub government: Option<String>,
+    #[serde(default)]
+    pub allegiance: Option<String>,
+    #[serde(default)]
+    pub states: Vec<String>,
+    #[serde(default)]
+    pub has_blackmarket: bool,
     //	has_commodities: Option<u8>,
-    //	has_refuel: Option<u8>,
-    //	has_rearm: Option<u8>,
-    //	has_shipyard: Option<u8>,
+    // None when the dump has no service data.  --refuel only excludes stations known to have none
+    #[serde(default)]
+    pub has_refuel: Option<bool>,
+    #[serde(default)]
+    pub has_repair: bool,
+    #[serde(default)]
+    pub has_rearm: bool,
+    #[serde(default)]
+    pub has_outfitting: bool,
+    #[serde(default)]
+    pub has_shipyard: bool,
     //	import_commodities: Box<Vec<String>>,
     //	export_commodities: Box<Vec<String>>,
-    //	economies: Box<Vec<String>>,,
+    #[serde(default)]
+    pub economies: Vec<String>,
     pub market_updated_at: Option<u64>,
     pub is_planetary: bool,
+    #[serde(default)]
+    pub market_id: Option<u64>,
+    #[serde(default)]
+    pub is_fleet_carrier: bool,
+    // set when the universe is filtered.  the player can start from an excluded station,
+    // but the search won't suggest it as a destination
+    #[serde(skip)]
+    pub is_excluded: bool,
+}
+
+impl Station {
+    // e.g. ['refuel', 'repair', 'rearm']
+    pub fn services(&self) -> Vec<&'static str> {
+        let services = [
+            (self.has_refuel == Some(true), "refuel"),
+            (self.has_repair, "repair"),
+            (self.has_rearm, "rearm"),
+            (self.has_outfitting, "outfitting"),
+            (self.has_shipyard, "shipyard"),
+            (self.has_blackmarket, "black market"),
+        ];
+
+        services
+            .iter()
+            .filter(|&&(has, _)| has)
+            .map(|&(_, name)| name)
+            .collect()
+    }
+
+    // the newest market data we have for the station - from the dump, EDDN, or the journal
+    pub fn market_age(&self, now: u64) -> u64 {
+        let newest_listing = self.listings.iter().map(|e| e.collected_at).max();
+        let updated_at = match (self.market_updated_at, newest_listing) {
+            (Some(a), Some(b)) => a.max(b),
+            (Some(a), None) => a,
+            (None, Some(b)) => b,
+            (None, None) => 0,
+        };
+
+        now.saturating_sub(updated_at)
+    }
 }
 
 impl Identified<u32> for Station {
@@ -101,8 +170,12 @@ pub struct Listing {
     pub supply: u32,
     pub buy_price: u32,
     pub sell_price: u32,
-    pub collected_at: u64, //	pub demand: u32,
-                           //	update_count: u16
+    pub collected_at: u64,
+    // a black market's offer for illegal goods.  only added to the universe when smuggling is enabled
+    #[serde(default)]
+    pub is_black_market: bool,
+    //	pub demand: u32,
+    //	update_count: u16
 }
 
 impl Listing {
@@ -120,6 +193,9 @@ pub struct Commodity {
     pub commodity_id: u16,
     pub commodity_name: String,
     pub category: String,
+    // the galactic average, which black markets pay for illegal goods
+    #[serde(default)]
+    pub average_price: Option<u32>,
 }
 
 impl PartialEq for Commodity {
@@ -134,6 +210,52 @@ impl Identified<u16> for Commodity {
     }
 }
 
+impl Commodity {
+    pub fn symbol(&self) -> String {
+        commodity_symbol(&self.commodity_name)
+    }
+}
+
+// the game refers to commodities by internal symbols (e.g. $agriculturalmedicines_name;),
+// while eddb uses display names (e.g. Agri-Medicines).
+// this reduces both forms to a lowercase alphanumeric key, so they can be compared.
+pub fn commodity_symbol(name: &str) -> String {
+    let name = name.trim().to_lowercase();
+    let name = name.trim_start_matches('$').trim_end_matches("_name;");
+
+    let symbol: String = name.chars().filter(|c| c.is_ascii_alphanumeric()).collect();
+
+    // game symbols which don't match the display name
+    match &symbol[..] {
+        "agriculturalmedicines" => "agrimedicines",
+        "atmosphericextractors" => "atmosphericprocessors",
+        "basicnarcotics" => "narcotics",
+        "comercialsamples" => "commercialsamples",
+        "coolinghoses" => "microweavecoolinghoses",
+        "drones" => "limpet",
+        "encripteddatastorage" => "encrypteddatastorage",
+        "hazardousenvironmentsuits" => "hesuits",
+        "heliostaticfurnaces" => "microbialfurnaces",
+        "lowtemperaturediamond" => "lowtemperaturediamonds",
+        "marinesupplies" => "marineequipment",
+        "mutomimager" => "muonimager",
+        "powergridassembly" => "energygridassembly",
+        "skimercomponents" => "skimmercomponents",
+        "terrainenrichmentsystems" => "landenrichmentsystems",
+        "trinketsoffortune" => "trinketsofhiddenfortune",
+        "usscargoancientartefact" => "ancientartefact",
+        "usscargoexperimentalchemicals" => "experimentalchemicals",
+        "usscargomilitaryplans" => "militaryplans",
+        "usscargoprototypetech" => "prototypetech",
+        "usscargorebeltransmissions" => "rebeltransmissions",
+        "usscargotechnicalblueprints" => "technicalblueprints",
+        "usscargotradedata" => "tradedata",
+        "wreckagecomponents" => "salvageablewreckage",
+        _ => &symbol[..],
+    }
+    .to_string()
+}
+
 #[derive(Serialize, Deserialize, Debug, Clone, PartialEq, PartialOrd)]
 pub enum ShipSize {
     Small,
@@ -141,6 +263,75 @@ pub enum ShipSize {
     Large,
 }
 
+impl ShipSize {
+    // landing pad size for the journal's ship identifiers (e.g. "type9", "krait_mkii")
+    pub fn from_hull(hull: &str) -> Option<ShipSize> {
+        match &hull.to_lowercase()[..] {
+            "adder" => Some(ShipSize::Small),
+            "cobramkiii" => Some(ShipSize::Small),
+            "cobramkiv" => Some(ShipSize::Small),
+            "cobramkv" => Some(ShipSize::Small),
+            "diamondback" => Some(ShipSize::Small),
+            "diamondbackxl" => Some(ShipSize::Small),
+            "dolphin" => Some(ShipSize::Small),
+            "eagle" => Some(ShipSize::Small),
+            "empire_courier" => Some(ShipSize::Small),
+            "empire_eagle" => Some(ShipSize::Small),
+            "hauler" => Some(ShipSize::Small),
+            "sidewinder" => Some(ShipSize::Small),
+            "viper" => Some(ShipSize::Small),
+            "viper_mkiv" => Some(ShipSize::Small),
+            "vulture" => Some(ShipSize::Small),
+
+            "asp" => Some(ShipSize::Medium),
+            "asp_scout" => Some(ShipSize::Medium),
+            "corsair" => Some(ShipSize::Medium),
+            "federation_dropship" => Some(ShipSize::Medium),
+            "federation_dropship_mkii" => Some(ShipSize::Medium),
+            "federation_gunship" => Some(ShipSize::Medium),
+            "ferdelance" => Some(ShipSize::Medium),
+            "independant_trader" => Some(ShipSize::Medium),
+            "krait_light" => Some(ShipSize::Medium),
+            "krait_mkii" => Some(ShipSize::Medium),
+            "lakonminer" => Some(ShipSize::Medium),
+            "mamba" => Some(ShipSize::Medium),
+            "mandalay" => Some(ShipSize::Medium),
+            "python" => Some(ShipSize::Medium),
+            "python_nx" => Some(ShipSize::Medium),
+            "type6" => Some(ShipSize::Medium),
+            "type8" => Some(ShipSize::Medium),
+            "typex" => Some(ShipSize::Medium),
+            "typex_2" => Some(ShipSize::Medium),
+            "typex_3" => Some(ShipSize::Medium),
+
+            "anaconda" => Some(ShipSize::Large),
+            "belugaliner" => Some(ShipSize::Large),
+            "cutter" => Some(ShipSize::Large),
+            "empire_trader" => Some(ShipSize::Large),
+            "explorer_nx" => Some(ShipSize::Large),
+            "federation_corvette" => Some(ShipSize::Large),
+            "orca" => Some(ShipSize::Large),
+            "panthermkii" => Some(ShipSize::Large),
+            "type7" => Some(ShipSize::Large),
+            "type9" => Some(ShipSize::Large),
+            "type9_military" => Some(ShipSize::Large),
+            _ => None,
+        }
+    }
+}
+
+impl Display for ShipSize {
+    fn fmt(&self, formatter: &mut Formatter) -> Result<(), Error> {
+        let str = match *self {
+            ShipSize::Small => "small",
+            ShipSize::Medium => "medium",
+            ShipSize::Large => "large",
+        };
+
+        formatter.write_str(str)
+    }
+}
+
 impl FromStr for ShipSize {
     type Err = String;
 
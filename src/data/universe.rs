@@ -5,13 +5,27 @@ use std::collections::HashMap;
 use std::fs::File;
 use std::path::Path;
 use std::path::PathBuf;
+use std::sync::Arc;
 
 use time::Duration;
 use time::PrimitiveDateTime;
 
+use crate::config::UserConfig;
+use crate::data::adjustments::AdjustmentLog;
+use crate::data::cache;
+use crate::data::cache::CacheRead;
+use crate::data::cache::CACHE_SCHEMA_VERSION;
 use crate::data::eddb::*;
+use crate::data::eddn::EddnCommodityMessage;
+use crate::data::filter::FilterOptions;
+use crate::data::legality;
+use crate::data::market_state;
+use crate::data::permits;
 use crate::data::price_adjustment::PriceAdjustment;
+use crate::data::source;
+use crate::data::source::MarketDataSource;
 use crate::data::time_adjustment::TimeAdjustment;
+use crate::data::time_adjustment::TIME_ADJUSTMENT_HISTORY;
 use crate::data::trader::*;
 use crate::data::universe_index::UniverseIndex;
 use crate::persist::*;
@@ -19,6 +33,7 @@ use crate::persist::*;
 use crate::search::FullTrade;
 
 use crate::search::SearchCache;
+use crate::util::fuzzy;
 use crate::util::scored_buf::ScoredCircularBuffer;
 use crate::util::scored_buf::Sort;
 
@@ -27,22 +42,30 @@ use filetime::FileTime;
 use statistical;
 use std::str::FromStr;
 
-fn get_cachefile_loc() -> PathBuf {
+pub fn get_cachefile_loc() -> PathBuf {
     get_base_directory().join(CACHE_FILENAME).to_path_buf()
 }
 
+// systems and stations are shared between snapshots,
+// and copied the first time a snapshot changes them.
+// the index only stores positions, which price changes don't move, so it is shared too.
+// adding or removing stations and listings copies it, if a snapshot shares it
 pub struct Universe {
-    pub systems: Vec<System>,
+    pub systems: Vec<Arc<System>>,
     pub time_adjustments: ScoredCircularBuffer<u64, TimeAdjustment>,
 
-    pub index: UniverseIndex,
+    pub index: Arc<UniverseIndex>,
 }
 
 impl Universe {
-    pub fn load(ship_size: &ShipSize) -> Universe {
+    pub fn load(filter: &FilterOptions, source: &Option<String>) -> Universe {
         let cachefile_path = get_cachefile_loc();
         let cachefile_str = cachefile_path.to_str().unwrap_or("<unknown>");
 
+        if !cachefile_path.exists() {
+            cache::migrate_legacy(&cachefile_path);
+        }
+
         let systems = match cachefile_path.exists() {
             true => {
                 let file = match File::open(&cachefile_path) {
@@ -65,43 +88,53 @@ impl Universe {
                 let num_hours = age.num_seconds() as f64 / 3600f64;
                 let threshold = Duration::days(1);
 
-                if age < threshold {
-                    println!(
-                        "Loading cached file from {} ... file cached {:.1} hours ago",
-                        cachefile_str, num_hours
-                    );
-                    read_json(&cachefile_path.as_path())
-                } else {
-                    println!("File was modified {} hours ago - refreshing", num_hours);
-                    Universe::recalculate_systems(&cachefile_path.as_path())
+                match (age < threshold, source) {
+                    (true, _) => {
+                        println!(
+                            "Loading cached file from {} ... file cached {:.1} hours ago",
+                            cachefile_str, num_hours
+                        );
+                        Universe::read_cache(&cachefile_path, source)
+                    }
+                    (false, &Some(ref source)) => {
+                        println!("File was modified {} hours ago - refreshing", num_hours);
+                        Universe::recalculate_systems(&cachefile_path.as_path(), source)
+                    }
+                    (false, &None) => {
+                        // a stale universe is much better than no universe
+                        println!(
+                            "File was modified {:.1} hours ago, but no data source is configured.  Prices may be out of date.",
+                            num_hours
+                        );
+                        println!("Provide the --source flag to refresh the universe.");
+                        Universe::read_cache(&cachefile_path, source)
+                    }
                 }
             }
-            false => Universe::recalculate_systems(&cachefile_path.as_path()),
+            false => Universe::rebuild_cache(&cachefile_path, source),
         };
 
-        let systems = Universe::filter_systems(systems, ship_size);
-        let index = UniverseIndex::calculate(&systems);
+        let systems = Universe::filter_systems(systems, filter);
+        let index = Arc::new(UniverseIndex::calculate(&systems));
 
         let mut universe = Universe {
             systems: systems,
-            time_adjustments: ScoredCircularBuffer::new(20, Sort::Descending),
+            time_adjustments: ScoredCircularBuffer::new(TIME_ADJUSTMENT_HISTORY, Sort::Descending),
             index: index,
         };
 
-        universe.apply_price_adjustments(PriceAdjustment::load_all());
-        universe.apply_time_adjustments(TimeAdjustment::load_all());
+        universe.apply_adjustment_log(&AdjustmentLog::load());
 
         universe
     }
 
+    // a copy which shares every system and station with this universe.
+    // changes to either one copy only the stations they touch
     pub fn snapshot(&self) -> Universe {
-        let systems_new = self.systems.clone();
-        let index = UniverseIndex::calculate(&systems_new);
-
         Universe {
-            systems: systems_new,
-            time_adjustments: ScoredCircularBuffer::new(20, Sort::Descending),
-            index: index,
+            systems: self.systems.clone(),
+            time_adjustments: ScoredCircularBuffer::new(TIME_ADJUSTMENT_HISTORY, Sort::Descending),
+            index: self.index.clone(),
         }
     }
 
@@ -118,29 +151,73 @@ impl Universe {
         cache.invalidate_station(trade.unit.buy_station.station_id);
     }
 
-    fn recalculate_systems(path: &Path) -> Vec<System> {
+    fn read_cache(path: &Path, source: &Option<String>) -> Vec<Arc<System>> {
+        match cache::read_systems(path) {
+            CacheRead::Current(systems) => systems,
+            CacheRead::Outdated(version) => {
+                println!(
+                    "Cached file uses schema version {}, but this version uses {}.",
+                    version, CACHE_SCHEMA_VERSION
+                );
+                Universe::rebuild_cache(path, source)
+            }
+            CacheRead::Invalid(reason) => {
+                println!("Cached file could not be read: {}", reason);
+                Universe::rebuild_cache(path, source)
+            }
+        }
+    }
+
+    fn rebuild_cache(path: &Path, source: &Option<String>) -> Vec<Arc<System>> {
+        match source {
+            &Some(ref source) => Universe::recalculate_systems(path, source),
+            &None => panic!(
+                "No usable cached universe exists at {}, and no data source is configured.  \
+                 Provide the --source flag, or set \"source\" in {}",
+                path.to_str().unwrap_or("<unknown>"),
+                UserConfig::get_path().to_str().unwrap_or("<unknown>")
+            ),
+        }
+    }
+
+    fn recalculate_systems(path: &Path, source: &String) -> Vec<Arc<System>> {
+        let source = source::from_config(source);
+
         println!(
-            "The cached data file is stale, or did not exist.  Reloading data from eddb.io ..."
+            "The cached data file is stale, or did not exist.  Reloading data from {} ...",
+            source.describe()
         );
 
-        println!("Loading commodities.json...");
-        let commodities_json: Vec<CommodityJson> =
-            http_read_json(&"https://eddb.io/archive/v6/commodities.json".to_string());
+        let dump = source.load();
 
-        println!("Loading system.json...");
-        let systems_json: Vec<SystemJson> =
-            http_read_json(&"https://eddb.io/archive/v6/systems_populated.json".to_string());
+        println!("Loads complete.  Converting to internal format...");
+        let systems = Universe::build_systems(dump);
 
-        println!("Loading stations.json...");
-        let stations_json: Vec<StationJson> =
-            http_read_json(&"https://eddb.io/archive/v6/stations.json".to_string());
+        println!("Saving cachefile to {} ...", path.to_str().unwrap());
+        cache::write_systems(path, &systems);
 
-        println!("Loading listings.csv...");
-        let listings_csv: Vec<StationCommodityListingJson> =
-            http_read_csv(&"https://eddb.io/archive/v6/listings.csv".to_string());
+        systems
+    }
 
-        println!("Listing 0: {:?}", listings_csv[0]);
-        println!("Loads complete.  Converting to internal format...");
+    // builds a universe directly from a source, skipping the cachefile and saved adjustments
+    #[allow(dead_code)]
+    pub fn from_source(source: &dyn MarketDataSource, filter: &FilterOptions) -> Universe {
+        let systems = Universe::build_systems(source.load());
+        let systems = Universe::filter_systems(systems, filter);
+        let index = Arc::new(UniverseIndex::calculate(&systems));
+
+        Universe {
+            systems: systems,
+            time_adjustments: ScoredCircularBuffer::new(TIME_ADJUSTMENT_HISTORY, Sort::Descending),
+            index: index,
+        }
+    }
+
+    fn build_systems(dump: EddbDump) -> Vec<Arc<System>> {
+        let commodities_json = dump.commodities;
+        let systems_json = dump.systems;
+        let stations_json = dump.stations;
+        let listings_csv = dump.listings;
 
         //	println!("Grouping stations by system");
         let stations_by_system = get_stations_by_system(&stations_json);
@@ -149,12 +226,27 @@ impl Universe {
         let mut commodities_by_id = HashMap::new();
         let mut commodities_by_name = HashMap::new();
 
+        // some dumps don't publish average prices, so they are estimated from the sell prices
+        let mut sell_prices: HashMap<u16, (u64, u64)> = HashMap::new();
+        for listing in &listings_csv {
+            if listing.sell_price > 0 {
+                let entry = sell_prices.entry(listing.commodity_id).or_insert((0, 0));
+                entry.0 += listing.sell_price as u64;
+                entry.1 += 1;
+            }
+        }
+
         //	println!("Calculating commodity lookups");
         for commodity_json in commodities_json {
+            let average_price = commodity_json.average_price.or(sell_prices
+                .get(&commodity_json.id)
+                .map(|&(total, count)| (total / count) as u32));
+
             let commodity = Box::new(Commodity {
                 commodity_id: commodity_json.id,
                 commodity_name: commodity_json.name,
                 category: commodity_json.category.name,
+                average_price: average_price,
             });
 
             commodities_by_id.insert(commodity_json.id, *commodity.clone());
@@ -167,8 +259,6 @@ impl Universe {
 
         //let mut stations_map = HashMap::new();
 
-        let now = PrimitiveDateTime::now().timestamp();
-
         for system_json in systems_json {
             let system_id = system_json.id;
             let mut system = Box::new(System {
@@ -178,8 +268,17 @@ impl Universe {
                 y: system_json.y,
                 z: system_json.z,
                 updated_at: system_json.updated_at,
-                needs_permit: system_json.needs_permit.unwrap_or(false),
+                needs_permit: system_json
+                    .needs_permit
+                    .unwrap_or_else(|| permits::is_permit_locked(&system_json.name)),
                 stations: Vec::new(),
+                controlling_faction: system_json.controlling_minor_faction.clone(),
+                government: system_json.government.clone(),
+                allegiance: system_json.allegiance.clone(),
+                population: system_json.population,
+                states: system_json.states.iter().map(|e| e.name.clone()).collect(),
+                security: system_json.security.clone(),
+                primary_economy: system_json.primary_economy.clone(),
             });
 
             match stations_by_system.get(&system_json.id) {
@@ -187,48 +286,22 @@ impl Universe {
                     for station_json in stations_jsons {
                         let station_id = station_json.id;
 
-                        // todo: exclude stations in a way that allows players to start trading from excluded stations
-
-                        if station_json.is_planetary.unwrap_or(false) {
-                            // todo: allow configuration of planetary stations
-                            continue;
-                        }
+                        // stations the player can't use are kept in the cache, and excluded by filter_systems.
+                        // this way the player can still start a route from one
 
-                        // Exclude fleet carriers
-                        if station_json.government == Some("Private Ownership".to_string()) {
-                            continue;
-                        }
-
-                        if let Some(market_updated_at) = station_json.market_updated_at {
-                            // based on lots of work in Mathematica, station sell prices significantly change,
-                            //   about 22 days after they collected
-
-                            // let's filter out any stations that haven't been updated in this time
-                            // this should prevent the player from being sent to stations for a loss
-
-                            // this technically should be recalculated on every run,
-                            //  but we recalculate the cachefile every day or two anyway
-
-                            //todo: dynamically scale buy/sell prices based on confidence during trade calculations
-
-                            let age = Duration::seconds(now as i64 - market_updated_at as i64);
-                            let threshold = Duration::days(22);
-
-                            if age > threshold {
-                                //println!("Rejecting {} as it was updated {} hours ago", station_json.name, age.num_hours());
-                                continue;
-                            }
-                        }
+                        let is_fleet_carrier =
+                            station_json.government == Some("Private Ownership".to_string());
 
                         let mut prohibited_commodities = Vec::new();
                         for commodity_name in &station_json.prohibited_commodities {
+                            // dumps which are built from markets only know the commodities which are traded.
+                            // a prohibited commodity nobody trades can't affect the search
                             let commodity =
                                 match commodities_by_name.get(&commodity_name.to_lowercase()) {
                                     Some(c) => c,
-                                    None => panic!(
-                                    "Unknown commodity '{}' in prohibited listing for station '{}'",
-                                    commodity_name, station_json.name
-                                ),
+                                    None => {
+                                        continue;
+                                    }
                                 };
 
                             prohibited_commodities.push(commodity.commodity_id);
@@ -263,6 +336,20 @@ impl Universe {
                             prohibited_commodities: prohibited_commodities,
                             market_updated_at: station_json.market_updated_at,
                             is_planetary: station_json.is_planetary.unwrap_or(false),
+                            is_fleet_carrier: is_fleet_carrier,
+                            faction: station_json.controlling_minor_faction.clone(),
+                            government: station_json.government.clone(),
+                            allegiance: station_json.allegiance.clone(),
+                            states: station_json.states.iter().map(|e| e.name.clone()).collect(),
+                            has_blackmarket: station_json.has_blackmarket.unwrap_or(false),
+                            has_refuel: station_json.has_refuel,
+                            has_repair: station_json.has_repair.unwrap_or(false),
+                            has_rearm: station_json.has_rearm.unwrap_or(false),
+                            has_outfitting: station_json.has_outfitting.unwrap_or(false),
+                            has_shipyard: station_json.has_shipyard.unwrap_or(false),
+                            economies: station_json.economies.clone(),
+                            is_excluded: false,
+                            market_id: station_json.market_id,
                         });
 
                         for listing_json in
@@ -292,47 +379,62 @@ impl Universe {
                                     true => listing_json.sell_price as u32,
                                     _ => 0,
                                 },
+                                is_black_market: false,
                             };
 
                             station.listings.push(listing);
                         }
 
-                        system.stations.push(*station);
+                        system.stations.push(Arc::new(*station));
                         //stations_map.insert( station.to_id(), *station );
                     }
                 }
                 None => {}
             };
 
-            systems.push(*system);
+            systems.push(Arc::new(*system));
         }
 
-        println!("Saving cachefile to {} ...", path.to_str().unwrap());
-        write_json(path, &systems);
-
-        return systems;
+        systems
     }
 
-    fn filter_systems(mut systems: Vec<System>, ship_size: &ShipSize) -> Vec<System> {
-        let _illegal_categories = ["drugs", "weapons", "slavery"];
-        let mut systems: Vec<System> = systems.drain(..).filter(|e| !e.needs_permit).collect();
+    // stations which don't pass the filter are marked as excluded, rather than removed.
+    // the player can still be docked at one, and start a route from it,
+    // but the search never sends them there
+    fn filter_systems(mut systems: Vec<Arc<System>>, filter: &FilterOptions) -> Vec<Arc<System>> {
+        let now = PrimitiveDateTime::now().timestamp() as u64;
+
+        // black markets will buy anything which is traded somewhere
+        let mut commodities: Vec<Commodity> = Vec::new();
+        if filter.smuggling {
+            let mut commodities_by_id = HashMap::new();
+            for station in systems.iter().flat_map(|e| e.stations.iter()) {
+                for listing in &station.listings {
+                    commodities_by_id
+                        .entry(listing.commodity.commodity_id)
+                        .or_insert(listing.commodity.clone());
+                }
+            }
 
-        for mut system in &mut systems {
-            let mut new_stations: Vec<Station> = system
-                .stations
-                .drain(..)
-                .filter(|e| e.max_landing_pad_size >= *ship_size)
-                .collect();
+            commodities = commodities_by_id.drain().map(|(_, v)| v).collect();
+            commodities.sort_by_key(|e| e.commodity_id);
+        }
 
-            for mut station in new_stations.iter_mut() {
-                if station.prohibited_commodities.len() == 0 {
-                    let new_listings: Vec<Listing> = station
-                        .listings
-                        .drain(..)
-                        //						.filter(|e| !illegal_categories.contains( &&e.commodity.category.to_lowercase()[..] ) )
-                        .collect();
-                    station.listings = new_listings;
-                } else {
+        for system in systems.iter_mut() {
+            // nothing shares the systems yet, so these don't copy
+            let system = Arc::make_mut(system);
+            let can_enter = filter.can_enter(system) && !filter.exclusions.excludes_system(system);
+            let mut new_stations: Vec<Arc<Station>> = system.stations.drain(..).collect();
+
+            for station in new_stations.iter_mut() {
+                let station = Arc::make_mut(station);
+                station.is_excluded = !can_enter
+                    || !filter.includes(station, now)
+                    || filter.avoids(system, station)
+                    || market_state::is_closed(system, station)
+                    || filter.exclusions.excludes_station(system, station);
+
+                if station.prohibited_commodities.len() > 0 {
                     let prohibited_commodities = &station.prohibited_commodities;
                     let new_listings: Vec<Listing> = station
                         .listings
@@ -341,6 +443,10 @@ impl Universe {
                         .collect();
                     station.listings = new_listings;
                 }
+
+                if filter.smuggling && station.has_blackmarket && !station.is_excluded {
+                    Universe::add_black_market_listings(&*system, station, &commodities);
+                }
             }
 
             system.stations = new_stations;
@@ -349,14 +455,170 @@ impl Universe {
         systems
     }
 
-    pub fn apply_price_adjustments(&mut self, prices: Vec<PriceAdjustment>) {
-        for price in prices {
-            self.apply_price_adjustment(&price);
+    // black market offers for the goods which are illegal at the station.
+    // the game doesn't publish black market prices, but they are close to the galactic average
+    fn add_black_market_listings(
+        system: &System,
+        station: &mut Station,
+        commodities: &Vec<Commodity>,
+    ) {
+        let collected_at = station.market_updated_at.unwrap_or(station.updated_at);
+
+        for commodity in commodities {
+            let sell_price = match commodity.average_price {
+                Some(price) if price > 0 => price,
+                _ => continue,
+            };
+
+            let is_listed = station
+                .listings
+                .iter()
+                .any(|e| e.commodity.commodity_id == commodity.commodity_id);
+
+            if is_listed || !legality::is_illegal(commodity, system, station) {
+                continue;
+            }
+
+            station.listings.push(Listing {
+                system_id: system.system_id,
+                station_id: station.station_id,
+                commodity: commodity.clone(),
+                supply: 0,
+                buy_price: 0,
+                sell_price: sell_price,
+                collected_at: collected_at,
+                is_black_market: true,
+            });
+        }
+    }
+
+    // keeps the search from suggesting the station.  the player can still start from it
+    pub fn exclude_station(&mut self, station_id: u32) {
+        if let Some(station) = self.get_station_mut(station_id) {
+            station.is_excluded = true;
+        }
+    }
+
+    // adds the system, replacing any system with the same id
+    pub fn add_system(&mut self, system: System) {
+        self.remove_system(system.system_id);
+
+        self.systems.push(Arc::new(system));
+        let system_index = self.systems.len() - 1;
+        Arc::make_mut(&mut self.index).insert_system(&self.systems, system_index);
+    }
+
+    pub fn remove_system(&mut self, system_id: u32) -> Option<Arc<System>> {
+        let system_index = self.index.get_index_system(system_id)?;
+        let index = Arc::make_mut(&mut self.index);
+
+        let removed = self.systems.swap_remove(system_index);
+        index.remove_system(&removed);
+
+        // the last system took its place
+        if let Some(moved) = self.systems.get(system_index) {
+            index.move_system(system_index, moved);
         }
+
+        Some(removed)
     }
 
-    pub fn apply_time_adjustments(&mut self, times: Vec<TimeAdjustment>) {
-        for time in &times {
+    // adds the station to its system, replacing any station with the same id.
+    // returns false if the system isn't in the universe
+    pub fn add_station(&mut self, station: Station) -> bool {
+        self.remove_station(station.station_id);
+
+        let system_index = match self.index.get_index_system(station.system_id) {
+            Some(i) => i,
+            None => {
+                return false;
+            }
+        };
+
+        let system = Arc::make_mut(&mut self.systems[system_index]);
+        system.stations.push(Arc::new(station));
+
+        let station_index = system.stations.len() - 1;
+        Arc::make_mut(&mut self.index).insert_station(
+            system_index,
+            station_index,
+            &system.stations[station_index],
+        );
+
+        true
+    }
+
+    pub fn remove_station(&mut self, station_id: u32) -> Option<Arc<Station>> {
+        let (system_index, station_index) = self.index.get_index_station(station_id)?;
+        let index = Arc::make_mut(&mut self.index);
+        let system = Arc::make_mut(&mut self.systems[system_index]);
+
+        let removed = system.stations.swap_remove(station_index);
+        index.remove_station(&removed);
+
+        if let Some(moved) = system.stations.get(station_index) {
+            index.remove_station(moved);
+            index.insert_station(system_index, station_index, moved);
+        }
+
+        Some(removed)
+    }
+
+    // adds the listing to its station, replacing any listing of the same commodity.
+    // returns false if the station isn't in the universe
+    pub fn add_listing(&mut self, listing: Listing) -> bool {
+        self.remove_listing(listing.station_id, listing.commodity.commodity_id);
+
+        let (system_index, station_index) = match self.index.get_index_station(listing.station_id)
+        {
+            Some(i) => i,
+            None => {
+                return false;
+            }
+        };
+
+        let system = Arc::make_mut(&mut self.systems[system_index]);
+        let station = Arc::make_mut(&mut system.stations[station_index]);
+        station.listings.push(listing);
+
+        let listing_index = station.listings.len() - 1;
+        Arc::make_mut(&mut self.index).insert_listing(
+            system_index,
+            station_index,
+            listing_index,
+            &station.listings[listing_index],
+        );
+
+        true
+    }
+
+    pub fn remove_listing(&mut self, station_id: u32, commodity_id: u16) -> Option<Listing> {
+        let (system_index, station_index, listing_index) =
+            self.index.get_index_listing(station_id, commodity_id)?;
+        let index = Arc::make_mut(&mut self.index);
+        let system = Arc::make_mut(&mut self.systems[system_index]);
+        let station = Arc::make_mut(&mut system.stations[station_index]);
+
+        let removed = station.listings.swap_remove(listing_index);
+        index.remove_listing(&removed);
+
+        if let Some(moved) = station.listings.get(listing_index) {
+            index.insert_listing(system_index, station_index, listing_index, moved);
+        }
+
+        Some(removed)
+    }
+
+    pub fn apply_adjustment_log(&mut self, log: &AdjustmentLog) {
+        for station_id in log.price_stations() {
+            if let Some(station) = self.get_station_mut(station_id) {
+                for price in log.prices_for_station(station_id) {
+                    Universe::apply_price_to_station(station, price);
+                }
+            }
+        }
+
+        for time in log.times() {
             self.apply_time_adjustment(time.clone());
         }
 
@@ -373,34 +635,134 @@ impl Universe {
 
     pub fn apply_price_adjustment(&mut self, price: &PriceAdjustment) {
         if let Some(station) = self.get_station_mut(price.station_id) {
-            for listing in station.listings.iter_mut() {
-                if price.commodity_id != listing.commodity.commodity_id {
-                    continue;
-                }
+            Universe::apply_price_to_station(station, price);
+        }
+    }
+
+    fn apply_price_to_station(station: &mut Station, price: &PriceAdjustment) {
+        for listing in station.listings.iter_mut() {
+            if price.commodity_id != listing.commodity.commodity_id {
+                continue;
+            }
 
-                // only overwrite if the timestamp of the adjustment is newer than the date from eddb
-                // this prevents old user-entered values from becoming stale.
-                // the adjustment has to be 10 minutes before the EDDB entry,
-                // in case we created the update using EDCE
-
-                if listing.collected_at < price.timestamp - 600 {
-                    match price.buy_price {
-                        Some(v) => listing.buy_price = v,
-                        None => {}
-                    };
-
-                    match price.supply {
-                        Some(v) => listing.supply = v,
-                        None => {}
-                    };
-
-                    match price.sell_price {
-                        Some(v) => listing.sell_price = v,
-                        None => {}
-                    };
+            // only overwrite if the timestamp of the adjustment is newer than the date from eddb
+            // this prevents old user-entered values from becoming stale.
+            if !price.is_superseded_by(listing) {
+                match price.buy_price {
+                    Some(v) => listing.buy_price = v,
+                    None => {}
+                };
+
+                match price.supply {
+                    Some(v) => listing.supply = v,
+                    None => {}
+                };
+
+                match price.sell_price {
+                    Some(v) => listing.sell_price = v,
+                    None => {}
+                };
+            }
+        }
+    }
+
+    // applies a market snapshot from EDDN.  returns the station id, and the number of listings which changed.
+    // a snapshot has the whole market, so commodities the station started trading are added,
+    // and the ones it stopped trading are removed
+    pub fn apply_eddn_message(&mut self, message: &EddnCommodityMessage) -> Option<(u32, usize)> {
+        let timestamp = match message.unix_seconds() {
+            Some(t) if t > 0 => t as u64,
+            _ => {
+                return None;
+            }
+        };
+
+        // dumps without market ids fall back to the station name
+        let station = match message.market_id {
+            Some(id) if self.get_station_by_market_id(id).is_some() => {
+                self.get_station_by_market_id_mut(id)
+            }
+            _ => self.get_station_by_name_mut(&message.system_name, &message.station_name),
+        };
+
+        let station = match station {
+            Some(s) => s,
+            None => {
+                return None;
+            }
+        };
+
+        let mut changed = 0;
+        let mut removed = Vec::new();
+        for listing in station.listings.iter_mut() {
+            // messages can arrive out of order, and the dump may be newer than the message
+            if listing.collected_at >= timestamp {
+                continue;
+            }
+
+            let listing_symbol = listing.commodity.symbol();
+            let commodity = message
+                .commodities
+                .iter()
+                .filter(|e| commodity_symbol(&e.name) == listing_symbol)
+                .next();
+
+            match commodity {
+                Some(commodity) => {
+                    listing.buy_price = commodity.buy_price;
+                    listing.sell_price = commodity.sell_price;
+                    listing.supply = commodity.stock;
+                    listing.collected_at = timestamp;
+                    changed += 1;
                 }
+                // black market offers never appear in the market data
+                None if !listing.is_black_market => removed.push(listing.commodity.commodity_id),
+                None => {}
+            }
+        }
+
+        let listed: Vec<String> = station
+            .listings
+            .iter()
+            .map(|e| e.commodity.symbol())
+            .collect();
+        let station_id = station.station_id;
+        let system_id = station.system_id;
+        let prohibited = station.prohibited_commodities.clone();
+
+        for commodity_id in removed {
+            self.remove_listing(station_id, commodity_id);
+            changed += 1;
+        }
+
+        for item in &message.commodities {
+            let symbol = commodity_symbol(&item.name);
+            if listed.contains(&symbol) {
+                continue;
             }
+
+            // commodities nobody lists yet can't be added - we don't know their id or category
+            let commodity = match self.index.get_commodity_by_symbol(&symbol) {
+                Some(c) if !prohibited.contains(&c.commodity_id) => c.clone(),
+                _ => {
+                    continue;
+                }
+            };
+
+            self.add_listing(Listing {
+                system_id: system_id,
+                station_id: station_id,
+                commodity: commodity,
+                supply: item.stock,
+                buy_price: item.buy_price,
+                sell_price: item.sell_price,
+                collected_at: timestamp,
+                is_black_market: false,
+            });
+            changed += 1;
         }
+
+        Some((station_id, changed))
     }
 
     pub fn apply_time_adjustment(&mut self, time: TimeAdjustment) {
@@ -514,38 +876,44 @@ impl Universe {
     }
 
     pub fn get_system_by_index<'a>(&'a self, index: Option<usize>) -> Option<&'a System> {
-        index.map(|i| &self.systems[i])
+        index.map(|i| &*self.systems[i])
     }
 
+    // copies the system first, if a snapshot shares it
     pub fn get_system_by_index_mut<'a>(
         &'a mut self,
         index: Option<usize>,
     ) -> Option<&'a mut System> {
-        index.map(move |i| &mut self.systems[i])
+        index.map(move |i| Arc::make_mut(&mut self.systems[i]))
     }
 
     pub fn get_systems_by_index<'a>(&'a self, indeces: Vec<usize>) -> Vec<&'a System> {
-        indeces.iter().map(|&index| &self.systems[index]).collect()
+        indeces.iter().map(|&index| &*self.systems[index]).collect()
     }
 
     pub fn get_station_by_index<'a>(
         &'a self,
         index: Option<(usize, usize)>,
     ) -> Option<&'a Station> {
-        index.map(|(sys, stat)| &self.systems[sys].stations[stat])
+        index.map(|(sys, stat)| &*self.systems[sys].stations[stat])
     }
 
+    // copies the station first, if a snapshot shares it.
+    // the system is copied too, but that only copies its list of stations
     pub fn get_station_by_index_mut<'a>(
         &'a mut self,
         index: Option<(usize, usize)>,
     ) -> Option<&'a mut Station> {
-        index.map(move |(sys, stat)| &mut self.systems[sys].stations[stat])
+        index.map(move |(sys, stat)| {
+            let system = Arc::make_mut(&mut self.systems[sys]);
+            Arc::make_mut(&mut system.stations[stat])
+        })
     }
 
     pub fn get_stations_by_index<'a>(&'a self, indeces: Vec<(usize, usize)>) -> Vec<&'a Station> {
         indeces
             .iter()
-            .map(|&(sys, stat)| &self.systems[sys].stations[stat])
+            .map(|&(sys, stat)| &*self.systems[sys].stations[stat])
             .collect()
     }
 
@@ -560,7 +928,10 @@ impl Universe {
         &'a mut self,
         index: Option<(usize, usize, usize)>,
     ) -> Option<&'a mut Listing> {
-        index.map(move |(sys, stat, list)| &mut self.systems[sys].stations[stat].listings[list])
+        index.map(move |(sys, stat, list)| {
+            let system = Arc::make_mut(&mut self.systems[sys]);
+            &mut Arc::make_mut(&mut system.stations[stat]).listings[list]
+        })
     }
 
     pub fn get_system(&self, id: u32) -> Option<&System> {
@@ -594,7 +965,7 @@ impl Universe {
             Some(system) => system
                 .stations
                 .iter()
-                .filter(|e| &e.station_name == station_name)
+                .filter(|e| e.station_name.eq_ignore_ascii_case(station_name.trim()))
                 .next(),
             _ => None,
         }
@@ -611,15 +982,67 @@ impl Universe {
                 .stations
                 .iter_mut()
                 .filter(|e| &e.station_name == station_name)
-                .next(),
+                .next()
+                .map(|e| Arc::make_mut(e)),
             _ => None,
         }
     }
 
+    pub fn get_station_by_market_id(&self, market_id: u64) -> Option<&Station> {
+        self.get_station_by_index(self.index.get_index_station_by_market_id(market_id))
+    }
+
+    pub fn get_station_by_market_id_mut(&mut self, market_id: u64) -> Option<&mut Station> {
+        let index = self.index.get_index_station_by_market_id(market_id);
+        self.get_station_by_index_mut(index)
+    }
+
     pub fn get_stations_by_name(&self, station_name: &String) -> Vec<&Station> {
         self.get_stations_by_index(self.index.get_index_station_by_name(station_name))
     }
 
+    // suggestions for a system name which wasn't found, best first
+    pub fn search_systems_by_name(&self, system_name: &String, limit: usize) -> Vec<&System> {
+        self.index
+            .search_index_systems_by_name(system_name, limit)
+            .into_iter()
+            .map(|i| &*self.systems[i])
+            .collect()
+    }
+
+    // suggestions for a station name which wasn't found, best first.
+    // if the system is known, its stations are searched first
+    pub fn search_stations_by_name(
+        &self,
+        system_name: Option<&String>,
+        station_name: &String,
+        limit: usize,
+    ) -> Vec<&Station> {
+        let mut stations = Vec::new();
+
+        if let Some(system) = system_name.and_then(|e| self.get_system_by_name(e)) {
+            stations = fuzzy::best_matches(
+                station_name,
+                system.stations.iter().map(|e| (&e.station_name, &**e)),
+                limit,
+            );
+        }
+
+        let indices = self.index.search_index_stations_by_name(station_name, limit);
+        let others = self.get_stations_by_index(indices);
+        for station in others {
+            if stations.len() >= limit {
+                break;
+            }
+
+            if !stations.iter().any(|e| e.station_id == station.station_id) {
+                stations.push(station);
+            }
+        }
+
+        stations
+    }
+
     //	pub fn get_listings_in_system( &self, id: &u16 ) -> Option<&Vec<Listing>> {
     //		self.listings_by_system.get( &id )
     //	}
@@ -632,3 +1055,367 @@ impl Universe {
         self.get_systems_by_index(self.index.get_index_systems_in_range(system, range))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::filter::Permit;
+    use crate::data::source::FixtureSource;
+
+    fn is_destination(universe: &Universe, station_id: u32) -> bool {
+        match universe.get_station(station_id) {
+            Some(station) => !station.is_excluded,
+            None => false,
+        }
+    }
+
+    #[test]
+    fn test_universe_from_fixture() {
+        let universe = Universe::from_source(
+            &FixtureSource::sample(),
+            &FilterOptions::new(ShipSize::Large),
+        );
+
+        // the permit system is excluded, but can still be found
+        assert_eq!(3, universe.systems.len());
+        assert!(universe
+            .get_system_by_name(&"Maiki".to_string())
+            .is_some());
+        assert!(!is_destination(&universe, 30));
+
+        let chono = universe
+            .get_system_by_name(&"chono".to_string())
+            .expect("Chono should be indexed");
+        assert_eq!(2, chono.system_id);
+
+        // the small outpost can't land a large ship, but the player can still start there
+        assert_eq!(2, chono.stations.len());
+        assert_eq!(
+            1,
+            universe
+                .get_stations_by_name(&"Small Outpost".to_string())
+                .len()
+        );
+        assert!(!is_destination(&universe, 21));
+        assert!(is_destination(&universe, 20));
+
+        // explosives are prohibited at Siddha Ring
+        let siddha = universe.get_station(20).expect("Siddha Ring should be indexed");
+        assert_eq!(1, siddha.listings.len());
+        assert_eq!("Gold", siddha.listings[0].commodity.commodity_name);
+        assert_eq!(10000, siddha.listings[0].sell_price);
+
+        let giger = universe.get_station(10).expect("Giger Hub should be indexed");
+        assert!(giger.listings.iter().all(|e| e.is_buy()));
+    }
+
+    #[test]
+    fn test_snapshot_shares_unchanged_stations() {
+        let universe = Universe::from_source(
+            &FixtureSource::sample(),
+            &FilterOptions::new(ShipSize::Large),
+        );
+
+        let mut snapshot = universe.snapshot();
+        assert!(Arc::ptr_eq(&universe.systems[1], &snapshot.systems[1]));
+        assert!(Arc::ptr_eq(&universe.index, &snapshot.index));
+
+        // only Siddha Ring, and the list of stations in Chono, are copied
+        snapshot.exclude_station(20);
+        assert!(snapshot.get_station(20).unwrap().is_excluded);
+        assert!(!universe.get_station(20).unwrap().is_excluded);
+
+        assert!(!Arc::ptr_eq(&universe.systems[1], &snapshot.systems[1]));
+        assert!(!Arc::ptr_eq(
+            &universe.systems[1].stations[0],
+            &snapshot.systems[1].stations[0]
+        ));
+        assert!(Arc::ptr_eq(
+            &universe.systems[1].stations[1],
+            &snapshot.systems[1].stations[1]
+        ));
+        assert!(Arc::ptr_eq(&universe.systems[0], &snapshot.systems[0]));
+    }
+
+    #[test]
+    fn test_incremental_index() {
+        let universe = Universe::from_source(
+            &FixtureSource::sample(),
+            &FilterOptions::new(ShipSize::Large),
+        );
+        let mut snapshot = universe.snapshot();
+
+        // Siddha Ring is swapped out, and the Small Outpost takes its place
+        let siddha = snapshot.remove_station(20).expect("Siddha Ring should be removed");
+        assert!(snapshot.get_station(20).is_none());
+        assert!(snapshot.get_stations_by_name(&"Siddha Ring".to_string()).is_empty());
+        assert_eq!(21, snapshot.get_station(21).unwrap().station_id);
+        assert_eq!(
+            21,
+            snapshot.get_station_by_market_id(3223343627).unwrap().station_id
+        );
+        assert!(snapshot
+            .get_listing_by_index(snapshot.get_index().get_index_listing(20, 1))
+            .is_none());
+
+        // the universe it was taken from still has it
+        assert_eq!(20, universe.get_station(20).unwrap().station_id);
+
+        // and it can be added back, with a new listing
+        let mut siddha = (*siddha).clone();
+        siddha.listings[0].sell_price = 12000;
+        assert!(snapshot.add_station(siddha));
+        let gold = snapshot
+            .get_listing_by_index(snapshot.get_index().get_index_listing(20, 1))
+            .unwrap();
+        assert_eq!(12000, gold.sell_price);
+
+        let mut explosives = gold.clone();
+        explosives.commodity = universe.get_station(10).unwrap().listings[1].commodity.clone();
+        explosives.sell_price = 500;
+        assert!(snapshot.add_listing(explosives));
+        assert_eq!(2, snapshot.get_station(20).unwrap().listings.len());
+
+        let removed = snapshot.remove_listing(20, 1).unwrap();
+        assert_eq!(12000, removed.sell_price);
+        let explosives = snapshot
+            .get_listing_by_index(snapshot.get_index().get_index_listing(20, 2))
+            .unwrap();
+        assert_eq!(500, explosives.sell_price);
+
+        // systems far from the others rebuild the octree, nearby ones are inserted
+        let mut colonia = (*universe.systems[0]).clone();
+        colonia.system_id = 4;
+        colonia.system_name = "Colonia".to_string();
+        colonia.x = -9530f64;
+        colonia.stations.clear();
+        snapshot.add_system(colonia);
+        let colonia = snapshot.get_system(4).unwrap();
+        assert_eq!(1, snapshot.get_systems_in_range(colonia, 10f64).len());
+
+        let maiki = snapshot.remove_system(3).unwrap();
+        assert!(snapshot.get_system(3).is_none());
+        assert!(snapshot.get_station(30).is_none());
+        let chono = snapshot.get_system(2).unwrap();
+        let ids: Vec<u32> = snapshot
+            .get_systems_in_range(chono, 15f64)
+            .iter()
+            .map(|e| e.system_id)
+            .collect();
+        assert!(!ids.contains(&maiki.system_id));
+        assert_eq!(
+            4,
+            snapshot
+                .get_system_by_name(&"Colonia".to_string())
+                .unwrap()
+                .system_id
+        );
+    }
+
+    #[test]
+    fn test_search_by_name() {
+        let universe = Universe::from_source(
+            &FixtureSource::sample(),
+            &FilterOptions::new(ShipSize::Large),
+        );
+
+        let systems = universe.search_systems_by_name(&"chnoo".to_string(), 5);
+        assert_eq!(1, systems.len());
+        assert_eq!("Chono", systems[0].system_name);
+
+        let stations = universe.search_stations_by_name(None, &"sidha rign".to_string(), 5);
+        assert_eq!(1, stations.len());
+        assert_eq!(20, stations[0].station_id);
+
+        // stations in the given system come first
+        let chono = "Chono".to_string();
+        let stations = universe.search_stations_by_name(Some(&chono), &"s".to_string(), 5);
+        let ids: Vec<u32> = stations.iter().map(|e| e.station_id).collect();
+        assert_eq!(vec![20, 21, 11], ids);
+    }
+
+    #[test]
+    fn test_systems_in_range_far_from_the_bubble() {
+        let mut source = FixtureSource::sample();
+
+        // two systems in Colonia, 4 ly apart
+        for &(id, x) in &[(4u32, -9530f64), (5, -9534f64)] {
+            let mut system = source.systems[0].clone();
+            system.id = id;
+            system.name = format!("Colonia {}", id);
+            system.x = x;
+            system.y = -910f64;
+            system.z = 19808f64;
+            source.systems.push(system);
+        }
+
+        let universe = Universe::from_source(&source, &FilterOptions::new(ShipSize::Large));
+        let colonia = universe.get_system(4).unwrap();
+        let ids: Vec<u32> = universe
+            .get_systems_in_range(colonia, 10f64)
+            .iter()
+            .map(|e| e.system_id)
+            .collect();
+        assert_eq!(2, ids.len());
+        assert!(ids.contains(&5));
+
+        let chono = universe.get_system(2).unwrap();
+        assert_eq!(3, universe.get_systems_in_range(chono, 15f64).len());
+    }
+
+    // two carriers in Chono, one seen 10 minutes ago, and one seen yesterday
+    fn add_carriers(source: &mut FixtureSource) {
+        let now = PrimitiveDateTime::now().timestamp() as u64;
+        for &(station_id, age) in &[(22u32, 600u64), (23, 86400)] {
+            let mut carrier = source.stations[2].clone();
+            carrier.id = station_id;
+            carrier.name = format!("Carrier {}", station_id);
+            carrier.government = Some("Private Ownership".to_string());
+            carrier.market_updated_at = Some(now - age);
+            carrier.market_id = None;
+            source.stations.push(carrier);
+        }
+    }
+
+    // Chono in lockdown, and no fuel at Giger Hub
+    fn add_states_and_services(source: &mut FixtureSource) {
+        source.systems[1].states = vec![StateJson {
+            name: "Lockdown".to_string(),
+        }];
+        for station in source.stations.iter_mut().filter(|e| e.id == 10) {
+            station.has_refuel = Some(false);
+        }
+    }
+
+    // a change to the fixture and the filter, and the stations which are destinations after it.
+    // the permit system, surface port and small outpost are only destinations when opted into
+    #[test]
+    fn test_universe_from_fixture_filters() {
+        let cases: &[(&str, fn(&mut FixtureSource), fn(&mut FilterOptions), &[u32])] = &[
+            ("defaults", |_| {}, |_| {}, &[10, 20]),
+            (
+                "small ship",
+                |_| {},
+                |filter| filter.ship_size = ShipSize::Small,
+                &[10, 20, 21],
+            ),
+            ("planetary", |_| {}, |filter| filter.planetary = true, &[10, 11, 20]),
+            (
+                "permit by name",
+                |_| {},
+                |filter| filter.permits = vec![Permit::Name("maiki".to_string())],
+                &[10, 20, 30],
+            ),
+            (
+                "permit by id",
+                |_| {},
+                |filter| filter.permits = vec![Permit::Id(3)],
+                &[10, 20, 30],
+            ),
+            (
+                "permit for another system",
+                |_| {},
+                |filter| filter.permits = vec![Permit::Name("Sol".to_string())],
+                &[10, 20],
+            ),
+            ("carriers by default", add_carriers, |_| {}, &[10, 20]),
+            (
+                "fresh carriers",
+                add_carriers,
+                |filter| filter.carrier_max_age = Some(2 * 3600),
+                &[10, 20, 22],
+            ),
+            // the metadata doesn't exclude anything by default
+            ("states and services", add_states_and_services, |_| {}, &[10, 20]),
+            (
+                "refuel and avoided states",
+                add_states_and_services,
+                |filter| {
+                    filter.require_refuel = true;
+                    filter.avoid_states = vec!["lockdown".to_string()];
+                },
+                &[],
+            ),
+            (
+                "refuel without service data",
+                |source| source.stations.iter_mut().for_each(|e| e.has_refuel = None),
+                |filter| filter.require_refuel = true,
+                &[10, 20],
+            ),
+        ];
+
+        for &(name, edit_source, edit_filter, destinations) in cases {
+            let mut source = FixtureSource::sample();
+            edit_source(&mut source);
+            let mut filter = FilterOptions::new(ShipSize::Large);
+            edit_filter(&mut filter);
+
+            let universe = Universe::from_source(&source, &filter);
+            for &station_id in &[10, 11, 20, 21, 22, 23, 30] {
+                assert_eq!(
+                    destinations.contains(&station_id),
+                    is_destination(&universe, station_id),
+                    "{}: station {}",
+                    name,
+                    station_id
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_universe_from_fixture_metadata() {
+        let mut source = FixtureSource::sample();
+        add_carriers(&mut source);
+        add_states_and_services(&mut source);
+
+        let mut filter = FilterOptions::new(ShipSize::Large);
+        filter.carrier_max_age = Some(2 * 3600);
+        let universe = Universe::from_source(&source, &filter);
+
+        let chono = universe.get_system(2).unwrap();
+        assert_eq!(vec!["Lockdown".to_string()], chono.states);
+        assert_eq!(Some("High".to_string()), chono.security);
+
+        let giger = universe.get_station(10).unwrap();
+        assert_eq!(vec!["repair", "rearm"], giger.services());
+        assert_eq!(vec!["Industrial".to_string()], giger.economies);
+
+        let surface_port = universe
+            .get_station(11)
+            .expect("Surface Port should be indexed");
+        assert!(surface_port.is_planetary);
+
+        let carrier = universe.get_station(22).expect("fresh carrier should be indexed");
+        assert!(carrier.is_fleet_carrier);
+    }
+
+    #[test]
+    fn test_universe_from_fixture_black_market() {
+        let mut source = FixtureSource::sample();
+        for station in source.stations.iter_mut().filter(|e| e.id == 20) {
+            station.has_blackmarket = Some(true);
+        }
+
+        // black market offers only exist when smuggling
+        let universe = Universe::from_source(&source, &FilterOptions::new(ShipSize::Large));
+        assert_eq!(1, universe.get_station(20).unwrap().listings.len());
+
+        let mut filter = FilterOptions::new(ShipSize::Large);
+        filter.smuggling = true;
+        let universe = Universe::from_source(&source, &filter);
+
+        let siddha = universe.get_station(20).unwrap();
+        assert_eq!(2, siddha.listings.len());
+
+        // explosives are prohibited at Siddha Ring, and priced at the average of the sell prices
+        let explosives = &siddha.listings[1];
+        assert!(explosives.is_black_market);
+        assert_eq!(2, explosives.commodity.commodity_id);
+        assert_eq!(290, explosives.sell_price);
+
+        // Giger Hub has no black market
+        let giger = universe.get_station(10).unwrap();
+        assert!(giger.listings.iter().all(|e| !e.is_black_market));
+    }
+}
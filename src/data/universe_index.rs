@@ -1,6 +1,8 @@
 extern crate time;
 
 use std::collections::HashMap;
+use std::collections::HashSet;
+use std::sync::Arc;
 
 use spatial::octree::Index;
 use spatial::octree::Octree;
@@ -8,12 +10,13 @@ use spatial::octree::Volume;
 
 use crate::data::trader::*;
 
+use crate::util::fuzzy;
 use crate::util::map_list::MapList;
 
 #[derive(Clone)]
 struct OctreeSystemPosition {
     pub loc: [f64; 3],
-    pub index: usize,
+    pub system_id: u32,
 }
 
 impl Index<f64> for OctreeSystemPosition {
@@ -22,93 +25,196 @@ impl Index<f64> for OctreeSystemPosition {
     }
 }
 
+// the index can be updated in place, as stations, listings and systems are added and removed.
+// listing and station changes only touch their own entries.
+//
+// the octree can't remove items, so it stores system ids instead of positions in the system list.
+// removed systems are skipped when the tree is searched,
+// and moving a system in the list doesn't affect the tree.
+// new systems are inserted, unless a snapshot shares the tree, or the system is outside it.
+// then the tree is rebuilt
+#[derive(Clone)]
 pub struct UniverseIndex {
     // lookups for high-performance access
     systems: HashMap<u32, usize>,
     systems_by_name: HashMap<String, usize>,
 
     // 3D spatial lookup.  SO COOL.
-    octree: Octree<f64, OctreeSystemPosition>,
+    octree: Arc<Octree<f64, OctreeSystemPosition>>,
+    octree_bounds: ([f64; 3], [f64; 3]),
+    // the systems which have a position in the tree, including removed ones
+    octree_systems: HashSet<u32>,
 
     // lookups for user interaction
     stations: HashMap<u32, (usize, usize)>,
     stations_by_name: MapList<String, (usize, usize)>,
+    stations_by_market_id: HashMap<u64, (usize, usize)>,
 
     listings: HashMap<(u32, u16), (usize, usize, usize)>,
+    commodities: HashMap<u16, Commodity>,
 }
 
 #[allow(dead_code)]
 impl<'a> UniverseIndex {
-    pub fn calculate(systems: &Vec<System>) -> UniverseIndex {
+    pub fn calculate(systems: &Vec<Arc<System>>) -> UniverseIndex {
         //		println!( "Found {} systems.  Generating indexes...", universe.systems.len() );
 
-        let mut systems_map = HashMap::new();
-        let mut systems_by_name = HashMap::new();
-        let mut listings = HashMap::new();
+        let mut index = UniverseIndex {
+            systems: HashMap::new(),
+            systems_by_name: HashMap::new(),
+            octree: Arc::new(Octree::new(Volume::new(DEFAULT_VOLUME_MIN, DEFAULT_VOLUME_MAX))),
+            octree_bounds: (DEFAULT_VOLUME_MIN, DEFAULT_VOLUME_MAX),
+            octree_systems: HashSet::new(),
+            stations: HashMap::new(),
+            stations_by_name: MapList::new(),
+            stations_by_market_id: HashMap::new(),
+            listings: HashMap::new(),
+            commodities: HashMap::new(),
+        };
 
-        for (index, system) in systems.iter().enumerate() {
-            systems_by_name.insert(system.system_name.to_lowercase(), index);
-            systems_map.insert(system.to_id(), index);
+        for (system_index, system) in systems.iter().enumerate() {
+            index.index_system(system_index, system);
         }
 
-        //	let mut system_name_map = HashMap::new();
-        //	for system in systems.clone() {
-        //		system_name_map.insert( system.system_name.to_lowercase(), system );
-        //	}
-
-        //	let mut listings_by_system = MapList::new();
-        //	for mut system in systems.clone() {
-        //		let system_id = system.to_id();
-        //		for mut station in system.stations.drain(..) {
-        //			for listing in station.listings.drain(..) {
-        //				listings_by_system.insert( system_id.clone(), listing );
-        //			}
-        //		}
-        //	}
-
-        let mut stations_map = HashMap::new();
-        let mut stations_by_name = MapList::new();
-        for (system_index, system) in systems.iter().enumerate() {
-            for (station_index, station) in system.stations.iter().enumerate() {
-                stations_by_name.insert(
-                    station.station_name.to_lowercase(),
-                    (system_index, station_index),
-                );
-                stations_map.insert(station.to_id(), (system_index, station_index));
-
-                for (listing_index, listing) in station.listings.iter().enumerate() {
-                    listings.insert(
-                        (station.to_id(), listing.commodity.commodity_id),
-                        (system_index, station_index, listing_index),
-                    );
-                }
+        index.rebuild_octree(systems);
+        index
+    }
+
+    // adds the system at the position in the list, with its stations and listings
+    pub fn insert_system(&mut self, systems: &Vec<Arc<System>>, system_index: usize) {
+        let system = &systems[system_index];
+        self.index_system(system_index, system);
+
+        let loc = [system.x, system.y, system.z];
+        let (min, max) = self.octree_bounds;
+        let inside = (0..3).all(|axis| loc[axis] >= min[axis] && loc[axis] <= max[axis]);
+
+        // a system which was removed and added again may have moved
+        let is_new = !self.octree_systems.contains(&system.system_id);
+
+        match (inside && is_new, Arc::get_mut(&mut self.octree)) {
+            (true, Some(octree)) => {
+                octree.insert(OctreeSystemPosition {
+                    loc: loc,
+                    system_id: system.system_id,
+                });
+                self.octree_systems.insert(system.system_id);
+            }
+            _ => self.rebuild_octree(systems),
+        }
+    }
+
+    // removes the system, its stations and listings.  the octree position is skipped from now on
+    pub fn remove_system(&mut self, system: &System) {
+        for station in &system.stations {
+            self.remove_station(station);
+        }
+
+        self.systems.remove(&system.system_id);
+        self.systems_by_name
+            .remove(&system.system_name.to_lowercase());
+    }
+
+    // the system is now at a new position in the list, e.g. after a swap_remove
+    pub fn move_system(&mut self, system_index: usize, system: &System) {
+        self.remove_system(system);
+        self.index_system(system_index, system);
+    }
+
+    pub fn insert_station(&mut self, system_index: usize, station_index: usize, station: &Station) {
+        let position = (system_index, station_index);
+
+        self.stations.insert(station.to_id(), position);
+        self.stations_by_name
+            .insert(station.station_name.to_lowercase(), position);
+        if let Some(market_id) = station.market_id {
+            self.stations_by_market_id.insert(market_id, position);
+        }
+
+        for (listing_index, listing) in station.listings.iter().enumerate() {
+            self.insert_listing(system_index, station_index, listing_index, listing);
+        }
+    }
+
+    pub fn remove_station(&mut self, station: &Station) {
+        for listing in &station.listings {
+            self.remove_listing(listing);
+        }
+
+        let position = match self.stations.remove(&station.station_id) {
+            Some(p) => p,
+            None => {
+                return;
             }
+        };
+
+        let name = station.station_name.to_lowercase();
+        let is_empty = match self.stations_by_name.get_mut(&name) {
+            Some(positions) => {
+                positions.retain(|e| *e != position);
+                positions.is_empty()
+            }
+            None => false,
+        };
+        if is_empty {
+            self.stations_by_name.remove(&name);
         }
 
-        //	let mut listings_by_station = MapList::new();
-        //	for mut system in systems.clone() {
-        //		for mut station in system.stations.drain(..) {
-        //			let station_id = station.to_id();
-        //			for listing in station.listings.drain(..) {
-        //				listings_by_station.insert( station_id.clone(), listing );
-        //			}
-        //		}
-        //	}
-
-        let octree = get_octree(&systems);
-
-        UniverseIndex {
-            systems: systems_map,
-            systems_by_name: systems_by_name,
-            stations: stations_map,
-            stations_by_name: stations_by_name,
-            listings: listings,
-            //		listings_by_system: listings_by_system,
-            //		listings_by_station: listings_by_station,
-            octree: octree,
+        if let Some(market_id) = station.market_id {
+            self.stations_by_market_id.remove(&market_id);
         }
     }
 
+    pub fn insert_listing(
+        &mut self,
+        system_index: usize,
+        station_index: usize,
+        listing_index: usize,
+        listing: &Listing,
+    ) {
+        self.listings.insert(
+            (listing.station_id, listing.commodity.commodity_id),
+            (system_index, station_index, listing_index),
+        );
+
+        self.commodities
+            .entry(listing.commodity.commodity_id)
+            .or_insert_with(|| listing.commodity.clone());
+    }
+
+    // the commodity stays known, so it can be listed again
+    pub fn remove_listing(&mut self, listing: &Listing) {
+        self.listings
+            .remove(&(listing.station_id, listing.commodity.commodity_id));
+    }
+
+    fn index_system(&mut self, system_index: usize, system: &System) {
+        self.systems.insert(system.to_id(), system_index);
+        self.systems_by_name
+            .insert(system.system_name.to_lowercase(), system_index);
+
+        for (station_index, station) in system.stations.iter().enumerate() {
+            self.insert_station(system_index, station_index, station);
+        }
+    }
+
+    fn rebuild_octree(&mut self, systems: &Vec<Arc<System>>) {
+        let (min, max) = get_bounds(systems);
+        let mut octree = Octree::new(Volume::new(min, max));
+
+        self.octree_systems.clear();
+        for system in systems {
+            octree.insert(OctreeSystemPosition {
+                loc: [system.x, system.y, system.z],
+                system_id: system.system_id,
+            });
+            self.octree_systems.insert(system.system_id);
+        }
+
+        self.octree = Arc::new(octree);
+        self.octree_bounds = (min, max);
+    }
+
     pub fn get_index_system(&self, id: u32) -> Option<usize> {
         self.systems.get(&id).map(|e| *e)
     }
@@ -123,6 +229,10 @@ impl<'a> UniverseIndex {
         self.stations.get(&id).map(|e| *e)
     }
 
+    pub fn get_index_station_by_market_id(&self, market_id: u64) -> Option<(usize, usize)> {
+        self.stations_by_market_id.get(&market_id).map(|e| *e)
+    }
+
     pub fn get_index_station_by_name(&self, station_name: &String) -> Vec<(usize, usize)> {
         self.stations_by_name
             .get(&station_name.to_lowercase())
@@ -138,11 +248,36 @@ impl<'a> UniverseIndex {
     //		self.listings_by_station.get( &id )
     //	}
 
+    // the closest system names to a possibly misspelled name, best first
+    pub fn search_index_systems_by_name(&self, system_name: &String, limit: usize) -> Vec<usize> {
+        fuzzy::best_matches(
+            system_name,
+            self.systems_by_name.iter().map(|(name, index)| (name, *index)),
+            limit,
+        )
+    }
+
+    // the closest station names to a possibly misspelled name, best first.
+    // every station with a matching name is included, so the result can exceed the limit
+    pub fn search_index_stations_by_name(
+        &self,
+        station_name: &String,
+        limit: usize,
+    ) -> Vec<(usize, usize)> {
+        let names: Vec<&Vec<(usize, usize)>> = fuzzy::best_matches(
+            station_name,
+            self.stations_by_name.iter().map(|(name, indices)| (name, indices)),
+            limit,
+        );
+
+        names.into_iter().flat_map(|e| e.iter().cloned()).collect()
+    }
+
     pub fn get_index_systems_in_range(&'a self, system: &System, range: f64) -> Vec<usize> {
         self.octree
             .get_in_radius(system.octree_index(), range)
             .into_iter()
-            .map(|e| e.index)
+            .filter_map(|e| self.get_index_system(e.system_id))
             .collect()
     }
 
@@ -153,19 +288,50 @@ impl<'a> UniverseIndex {
     ) -> Option<(usize, usize, usize)> {
         self.listings.get(&(station_id, commodity_id)).map(|e| *e)
     }
+
+    // any commodity which has been listed somewhere, by symbol (see trader::commodity_symbol)
+    pub fn get_commodity_by_symbol(&self, symbol: &String) -> Option<&Commodity> {
+        self.commodities.values().find(|e| &e.symbol() == symbol)
+    }
+
+    // the closest commodity names to a possibly misspelled name, best first
+    pub fn search_commodities_by_name(&self, name: &String, limit: usize) -> Vec<&Commodity> {
+        fuzzy::best_matches(
+            name,
+            self.commodities.values().map(|e| (&e.commodity_name, e)),
+            limit,
+        )
+    }
 }
 
-fn get_octree(systems: &Vec<System>) -> Octree<f64, OctreeSystemPosition> {
-    let volume: Volume<f64> =
-        Volume::new([-2700f64, -1000f64, -1400f64], [1600f64, 1200f64, 4000f64]);
-    let mut octree = Octree::new(volume);
+// the bubble, used when there are no systems to measure
+const DEFAULT_VOLUME_MIN: [f64; 3] = [-2700f64, -1000f64, -1400f64];
+const DEFAULT_VOLUME_MAX: [f64; 3] = [1600f64, 1200f64, 4000f64];
+
+// padding around the outermost systems, so range queries from the edge stay inside the tree
+const VOLUME_MARGIN: f64 = 500f64;
+
+// the volume is measured from the systems, so Colonia and other far regions are indexed.
+// the octree only subdivides where there are systems, so the empty space between regions is cheap
+fn get_bounds(systems: &Vec<Arc<System>>) -> ([f64; 3], [f64; 3]) {
+    if systems.is_empty() {
+        return (DEFAULT_VOLUME_MIN, DEFAULT_VOLUME_MAX);
+    }
+
+    let mut min = [std::f64::MAX; 3];
+    let mut max = [std::f64::MIN; 3];
+    for system in systems {
+        let loc = [system.x, system.y, system.z];
+        for axis in 0..3 {
+            min[axis] = min[axis].min(loc[axis]);
+            max[axis] = max[axis].max(loc[axis]);
+        }
+    }
 
-    for (index, system) in systems.iter().enumerate() {
-        octree.insert(OctreeSystemPosition {
-            loc: [system.x, system.y, system.z],
-            index: index,
-        });
+    for axis in 0..3 {
+        min[axis] -= VOLUME_MARGIN;
+        max[axis] += VOLUME_MARGIN;
     }
 
-    octree
+    (min, max)
 }
@@ -1,5 +1,5 @@
-// todo: delete old universe cachefiles
 mod arguments;
+mod config;
 mod data;
 mod messages;
 mod persist;
@@ -9,18 +9,39 @@ mod util;
 
 use getopts::{Matches, Options};
 use std::str::FromStr;
+use std::sync::mpsc::Receiver;
 use time::PreciseTime;
 
 use arguments::Arguments;
-use data::EdceData;
+use config::UserConfig;
+use data::eddn::EddnListener;
+use data::exclusions::Exclusions;
+use data::filter::FilterOptions;
+use data::housekeeping;
+use data::legality;
+use data::journal::JournalEvent;
+use data::journal::JournalMarketBuy;
+use data::journal::JournalMarketSell;
+use data::trader::ShipSize;
+use data::trader::Station;
+use data::trader::System;
+use data::trader::commodity_symbol;
+use data::JournalData;
+use data::JournalWatcher;
+use data::WatchEvent;
 use data::PriceAdjustment;
 use data::TimeAdjustment;
 use data::Universe;
 use messages::*;
+use search::FullTrade;
 use search::PlayerState;
 use search::SearchCache;
 use search::SearchQuality;
 use search::SearchStation;
+use search::market_query::MarketQuery;
+use search::market_query::MarketQueryKind;
+use search::hold_sale::HoldSale;
+use search::hold_sale::HoldSaleQuery;
 use time::PrimitiveDateTime;
 use util::num_unit::*;
 
@@ -29,8 +50,12 @@ use user_input::prompt_value;
 const VERSION: &'static str = env!("CARGO_PKG_VERSION");
 pub const SEPARATOR: &'static str =
     "-------------------------------------------------------------------";
-pub const CACHE_FILENAME: &'static str =
-    concat!("elite_universe_", env!("CARGO_PKG_VERSION"), ".min.json");
+pub const CACHE_FILENAME: &'static str = "elite_universe.cache";
+
+// --find, --find-buyer and --sell-hold search this far, unless --within is given
+const DEFAULT_QUERY_RANGE: f64 = 50f64;
+const MARKET_QUERY_LIMIT: usize = 20;
+const HOLD_SALE_LIMIT: usize = 10;
 
 fn options() -> Options {
     let mut opts = Options::new();
@@ -73,17 +98,117 @@ fn options() -> Options {
         "searches to the given hop length and prints stats",
         "12",
     );
+    opts.optflagopt(
+        "J",
+        "journal",
+        "enables Player Journal integration - \
+		automatically sets all user state, including ship size and jump range, when enabled.  \
+		defaults to Saved Games\\Frontier Developments\\Elite Dangerous",
+        "<full path to journal directory>",
+    );
     opts.optopt(
-        "C",
-        "edce",
-        "enables Elite Dangerous Companion Emulator integration - \
-		automatically sets all user state (except for jump range) when enabled",
-        "<full path to emulator directory>",
+        "S",
+        "source",
+        "market data used to refresh the universe - a directory with eddb-style dumps \
+		(commodities.json, systems_populated.json, stations.json, listings.csv), \
+		a spansh galaxy_populated.json file, or the url of an eddb mirror.  can also be set in config.json",
+        "<full path to dump directory or file>",
+    );
+
+    opts.optopt(
+        "E",
+        "eddn",
+        "applies EDDN commodity messages to the universe as they arrive - \
+		from a newline-delimited JSON file, - for stdin, or a tcp:// relay \
+		(requires the eddn-zmq feature)",
+        "<file, -, or tcp://eddn.edcd.io:9500>",
     );
 
     opts.optflag("i", "timetables", "prints time tables");
+    opts.optflag(
+        "",
+        "smuggling",
+        "sells illegal goods on black markets.  expected fines are set by smuggling_risk in config.json",
+    );
+    opts.optopt(
+        "",
+        "find",
+        "lists the nearest stations which sell the commodity, by travel time",
+        "Gold",
+    );
+    opts.optopt(
+        "",
+        "find-buyer",
+        "lists the nearest stations which buy the commodity, by travel time",
+        "Gold",
+    );
+    opts.optopt(
+        "",
+        "min-supply",
+        "with --find, the smallest supply to list (default 1)",
+        "500",
+    );
+    opts.optopt(
+        "",
+        "min-price",
+        "with --find-buyer, the lowest sell price to list",
+        "9.5k",
+    );
+    opts.optopt(
+        "",
+        "within",
+        "with --find, --find-buyer or --sell-hold, the search radius in light years (default 50).  \
+		also the range to sell cargo aboard when a route starts",
+        "50",
+    );
+    opts.optopt(
+        "",
+        "hold",
+        "cargo already in your hold, as commodity:tons pairs, comma separated.  \
+		routes start by selling it.  read from Cargo.json with journal integration",
+        "\"Gold:20,Painite:4\"",
+    );
+    opts.optflag(
+        "",
+        "sell-hold",
+        "lists the best stations to sell the cargo in your hold, by credits per minute",
+    );
+    opts.optflag(
+        "",
+        "refuel",
+        "only routes through stations which sell fuel",
+    );
+    opts.optopt(
+        "",
+        "avoid",
+        "avoids stations and systems in these faction states, comma separated.  \
+		can also be set as avoid_states in config.json",
+        "\"Lockdown,Civil War\"",
+    );
+    opts.optflag(
+        "",
+        "gc",
+        "removes old universe caches and adjustments which no longer apply, and prints a report",
+    );
     opts.optflag("h", "help", "prints this help menu");
     opts.optflag("A", "autoaccept", "automatically accepts trade options");
+    opts.optflag(
+        "P",
+        "planetary",
+        "includes planetary ports and settlements in routes.  requires Horizons or Odyssey",
+    );
+    opts.optflagopt(
+        "C",
+        "carriers",
+        "includes fleet carrier markets updated within the given number of hours (default 2).  \
+		routes end at a carrier, as its orders can change",
+        "<hours>",
+    );
+    opts.optflag(
+        "w",
+        "watch",
+        "tails the Player Journal, and completes trade prompts automatically when you buy and sell",
+    );
     opts
 }
 
@@ -96,7 +221,7 @@ fn main() {
     println!("Use the -h or --help flags for instructions,\n visit https://github.com/austinjones/elitetrader/");
     println!("");
 
-    println!("Thank you to to Paul Heisig and the maintainers of\n http://eddb.io/ for hosting the data used by this tool!");
+    println!("Thank you to to Paul Heisig and the maintainers of\n http://eddb.io/, and to the spansh.co.uk and EDDN\n maintainers for the data used by this tool!");
     println!("");
 
     println!("This software is distributed under the GNU General Public License:");
@@ -125,16 +250,42 @@ fn main() {
     println!("Loading Elite Dangerous universe data...");
     println!("");
 
-    let edce_data = EdceData::generate_opt(&opt_vals.opt_str("C"));
-    let arguments = Arguments::collect(&opt_vals, &edce_data);
+    let user_config = UserConfig::load();
+
+    if opt_vals.opt_present("gc") {
+        run_gc(&opt_vals, &user_config);
+        return;
+    }
+
+    let journal_path = Arguments::get_journal_path(&opt_vals);
+    let journal_data = JournalData::generate_opt(&journal_path);
+    let arguments = Arguments::collect(&opt_vals, &journal_data, &user_config);
 
-    let mut universe = Universe::load(&arguments.ship_size);
+    let mut universe = Universe::load(&arguments.filter_options(), &arguments.source);
+
+    let report = housekeeping::run(&universe);
+    if report.items_removed() > 0 {
+        println!(
+            "Removed {} old caches and adjustments.  Use --gc for details.",
+            report.items_removed()
+        );
+    }
     let player_state = PlayerState::new(&arguments, &universe);
 
     println!("");
     println!("Universe loaded!");
     println!("{}", SEPARATOR);
 
+    if opt_vals.opt_present("find") || opt_vals.opt_present("find-buyer") {
+        run_market_query(&opt_vals, &universe, &player_state);
+        return;
+    }
+
+    if opt_vals.opt_present("sell-hold") {
+        run_hold_sale(&opt_vals, &universe, &player_state);
+        return;
+    }
+
     match opt_vals.opt_str("d") {
         Some(str) => {
             let depth = match usize::from_str(&str[..]) {
@@ -159,9 +310,346 @@ fn main() {
                 &arguments,
                 &player_state,
                 arguments.search_quality,
+                parse_range(&opt_vals),
+            );
+        }
+    }
+}
+
+fn run_gc(config: &Matches, user_config: &UserConfig) {
+    // small ships can land everywhere, so every station is checked
+    let source = Arguments::get_source(config, user_config);
+    let mut filter = FilterOptions::new(ShipSize::Small);
+    filter.planetary = true;
+    let universe = Universe::load(&filter, &source);
+
+    println!("{}", SEPARATOR);
+    housekeeping::run(&universe).print();
+    println!("{}", SEPARATOR);
+}
+
+fn run_market_query(config: &Matches, universe: &Universe, player_state: &PlayerState) {
+    let (commodity_name, kind) = match config.opt_str("find") {
+        Some(name) => {
+            let min_supply = parse_num(config, "min-supply", 1);
+            (name, MarketQueryKind::Supply(min_supply.max(1)))
+        }
+        None => {
+            let name = config.opt_str("find-buyer").unwrap();
+            let min_price = parse_num(config, "min-price", 0);
+            (name, MarketQueryKind::Demand(min_price))
+        }
+    };
+
+    let commodity_name = commodity_name.replace("\"", "");
+    let commodity = match universe
+        .get_index()
+        .get_commodity_by_symbol(&commodity_symbol(&commodity_name))
+    {
+        Some(c) => c.clone(),
+        None => {
+            println!("The commodity '{}' was not found.", commodity_name);
+            let suggestions = universe
+                .get_index()
+                .search_commodities_by_name(&commodity_name, 5);
+            if !suggestions.is_empty() {
+                let names: Vec<&str> =
+                    suggestions.iter().map(|e| &e.commodity_name[..]).collect();
+                println!("Similar commodities: {}", names.join(", "));
+            }
+            return;
+        }
+    };
+
+    let within = parse_range(config);
+
+    let query = MarketQuery {
+        commodity: commodity,
+        kind: kind,
+        within: within,
+        limit: MARKET_QUERY_LIMIT,
+    };
+    let results = query.run(universe, player_state);
+
+    let verb = match query.kind {
+        MarketQueryKind::Supply(_) => "selling",
+        MarketQueryKind::Demand(_) => "buying",
+    };
+    println!(
+        "{} stations {} {} within {:.0} ly:",
+        results.len(),
+        verb,
+        query.commodity.commodity_name,
+        within
+    );
+    println!("");
+
+    println!("mins\tly\tls\tprice\tsupply\tsystem [station]");
+    for result in &results {
+        let price = match query.kind {
+            MarketQueryKind::Supply(_) => result.listing.buy_price,
+            MarketQueryKind::Demand(_) => result.listing.sell_price,
+        };
+
+        println!(
+            "{:.1}\t{:.1}\t{:.0}\t{}\t{}\t{} [{}]{}",
+            result.time.time_total / 60f64,
+            result.time.distance_to_system,
+            result.time.distance_to_station,
+            NumericUnit::new_string(price, &"cr".to_string()),
+            NumericUnit::new_string(result.listing.supply, &"tn".to_string()),
+            result.system.system_name,
+            result.station.station_name,
+            station_label(result.station)
+        );
+    }
+
+    println!("{}", SEPARATOR);
+}
+
+fn run_hold_sale(config: &Matches, universe: &Universe, player_state: &PlayerState) {
+    if player_state.held_cargo.is_empty() {
+        println!("Your hold is empty.  Use --hold to list your cargo, e.g. --hold \"Gold:20\"");
+        return;
+    }
+
+    let query = HoldSaleQuery {
+        within: parse_range(config),
+        limit: HOLD_SALE_LIMIT,
+    };
+    let results = query.run(universe, player_state);
+
+    println!(
+        "{} stations buy your cargo within {:.0} ly:",
+        results.len(),
+        query.within
+    );
+
+    for result in &results {
+        println!("{}", SEPARATOR);
+        println!(
+            "{} [{}]{} - {:.1} mins, {:.1} ly, {:.0} ls",
+            result.system.system_name,
+            result.station.station_name,
+            station_label(result.station),
+            result.time.time_total / 60f64,
+            result.time.distance_to_system,
+            result.time.distance_to_station
+        );
+        println!(
+            "Sells for {} ({}/min)",
+            NumericUnit::new_string(result.credits_total, &"cr".to_string()),
+            NumericUnit::new_string(result.credits_per_min() as u32, &"cr".to_string())
+        );
+
+        for (listing, tons) in &result.sales {
+            let market = match listing.is_black_market {
+                true => " on the black market",
+                false => "",
+            };
+
+            println!(
+                "\t{} {} at {}{}",
+                NumericUnit::new_string(*tons, &"tn".to_string()),
+                listing.commodity.commodity_name,
+                NumericUnit::new_string(listing.sell_price, &"cr".to_string()),
+                market
+            );
+        }
+
+        for held in &result.unsold {
+            println!(
+                "\t{} {} is not bought here",
+                NumericUnit::new_string(held.tons, &"tn".to_string()),
+                held.commodity.commodity_name
             );
         }
     }
+
+    println!("{}", SEPARATOR);
+}
+
+// the suggested commodity, bought at the suggested station
+fn is_trade_purchase(buy: &JournalMarketBuy, trade: &FullTrade) -> bool {
+    let is_station = match trade.unit.buy_station.market_id {
+        Some(market_id) => market_id == buy.market_id,
+        None => true,
+    };
+
+    is_station && commodity_symbol(&buy.commodity) == trade.unit.buy.commodity.symbol()
+}
+
+// the suggested commodity, sold at the suggested station
+fn is_trade_sale(sell: &JournalMarketSell, trade: &FullTrade) -> bool {
+    let is_station = match trade.unit.sell_station.market_id {
+        Some(market_id) => market_id == sell.market_id,
+        None => true,
+    };
+
+    is_station && commodity_symbol(&sell.commodity) == trade.unit.sell.commodity.symbol()
+}
+
+// a sale of one of the commodities we planned to sell, at the planned station
+fn is_hold_sale(sell: &JournalMarketSell, sale: &HoldSale) -> bool {
+    let is_station = match sale.station.market_id {
+        Some(market_id) => market_id == sell.market_id,
+        None => true,
+    };
+
+    let symbol = commodity_symbol(&sell.commodity);
+    is_station
+        && sale
+            .sales
+            .iter()
+            .any(|(listing, _)| listing.commodity.symbol() == symbol)
+}
+
+// the first hop of a route started with cargo aboard.
+// Some(true) once the cargo is sold, Some(false) to keep it aboard, and None to quit
+fn run_hold_sale_hop(
+    sale: &HoldSale,
+    hop: usize,
+    watcher: &mut Option<JournalWatcher>,
+    input: &Option<Receiver<String>>,
+) -> Option<bool> {
+    let now = PrimitiveDateTime::now();
+    println!(
+        "hop {}:\t{}, sell your hold for {} over {:.1} minutes",
+        hop,
+        now.format("%I:%M%p").trim(),
+        NumericUnit::new_string(sale.credits_total, &"cr".to_string()),
+        sale.time.time_total / 60f64
+    );
+    println!("");
+
+    println!(
+        "sell:\t{} [{}]{}",
+        sale.system.system_name,
+        sale.station.station_name,
+        station_label(sale.station)
+    );
+    print_station_details(sale.system, sale.station);
+
+    for (listing, tons) in &sale.sales {
+        println!(
+            "\t{} [{}] at {} x {}",
+            listing.commodity.category,
+            listing.commodity.commodity_name,
+            NumericUnit::new_string(listing.sell_price, &"cr".to_string()),
+            tons
+        );
+    }
+
+    for held in &sale.unsold {
+        println!(
+            "\t{} x {} stays aboard",
+            held.commodity.commodity_name, held.tons
+        );
+    }
+
+    println!("");
+    println!(
+        "\t{:.0} ly to system [{:.1} mins]",
+        sale.time.distance_to_system,
+        sale.time.time_to_system / 60f64
+    );
+    println!(
+        "\t{:.0} ls to station [{:.1} mins]",
+        sale.time.distance_to_station,
+        sale.time.time_to_station / 60f64
+    );
+    println!("");
+
+    if let (Some(watcher), Some(input)) = (watcher.as_mut(), input.as_ref()) {
+        println!(
+            "end:\twaiting for a sale at {} [{}] ...",
+            sale.system.system_name, sale.station.station_name
+        );
+        println!("\tk) to keep your cargo aboard, and trade with the space left");
+        println!("\tq) to quit");
+
+        // the hop ends once each commodity has been sold, in one lot or several
+        let mut unsold: Vec<String> = sale
+            .sales
+            .iter()
+            .map(|(listing, _)| listing.commodity.symbol())
+            .collect();
+
+        loop {
+            let event = watcher.wait_for_or_input(input, |event| {
+                if let &JournalEvent::MarketSell(ref sell) = event {
+                    if is_hold_sale(sell, sale) {
+                        let symbol = commodity_symbol(&sell.commodity);
+                        unsold.retain(|e| *e != symbol);
+                    }
+                }
+
+                unsold.is_empty()
+            });
+
+            match event {
+                WatchEvent::Journal(_) => return Some(true),
+                WatchEvent::Input(line) => match &line[..] {
+                    "k" | "keep" => return Some(false),
+                    "q" | "quit" => return None,
+                    _ => println!("\tsell your cargo, or enter k or q"),
+                },
+            }
+        }
+    }
+
+    // there's no start prompt to skip, so the sale is confirmed here even with --autoaccept
+
+    println!("end:\tenter) once you've sold your cargo");
+    println!("\tk) to keep your cargo aboard, and trade with the space left");
+    println!("\tq) to quit");
+
+    match &user_input::read_line()[..] {
+        "k" | "keep" => Some(false),
+        "q" | "quit" => None,
+        _ => Some(true),
+    }
+}
+
+// saved, so the station isn't suggested in later sessions either
+fn exclude_sell_station(
+    trade: &FullTrade,
+    universe: &mut Universe,
+    exclusions: &mut Exclusions,
+    search_cache: &SearchCache,
+) {
+    exclusions.add_station(trade.unit.sell_system, trade.unit.sell_station);
+    exclusions.save();
+
+    universe.exclude_station(trade.unit.sell_station.station_id);
+    search_cache.invalidate_station(trade.unit.sell_station.station_id);
+
+    println!(
+        "exclude:\t{} [{}] won't be suggested again.  Edit {} to undo this.",
+        trade.unit.sell_system.system_name,
+        trade.unit.sell_station.station_name,
+        Exclusions::get_path().to_str().unwrap_or("<unknown>")
+    );
+}
+
+fn parse_range(config: &Matches) -> f64 {
+    match config.opt_str("within") {
+        Some(v) => match f64::from_str(&v[..]) {
+            Ok(v) => v,
+            Err(reason) => panic!("Invalid search radius '{}' - {}", v, reason),
+        },
+        None => DEFAULT_QUERY_RANGE,
+    }
+}
+
+fn parse_num(config: &Matches, name: &str, default: u32) -> u32 {
+    match config.opt_str(name) {
+        Some(v) => match NumericUnit::from_str(&v[..]) {
+            Ok(v) => v.to_num(),
+            Err(reason) => panic!("Invalid {} '{}' - {}", name, v, reason),
+        },
+        None => default,
+    }
 }
 
 fn run_timetables(config: &Matches) {
@@ -227,7 +715,9 @@ fn run_diagnostic(universe: &mut Universe, state_in: &PlayerState, search_qualit
             result.trade.unit.adjusted_time.time_total / 60f64,
             result.trade.profit_total,
             result.trade.unit.commodity_name,
-            if result.trade.unit.sell_station.is_planetary {
+            if result.trade.unit.sell_station.is_fleet_carrier {
+                "carrier"
+            } else if result.trade.unit.sell_station.is_planetary {
                 "planetary"
             } else {
                 "station"
@@ -336,6 +826,7 @@ fn run_search(
     args: &Arguments,
     state_in: &PlayerState,
     search_quality: SearchQuality,
+    hold_range: f64,
 ) {
     let hop_width = search_quality.get_hop_width();
     let depth = search_quality.get_depth();
@@ -359,20 +850,86 @@ fn run_search(
     let start_state = state_in.clone();
     let mut player_state = state_in.clone();
 
-    let is_edce = args.edce_path.is_some();
+    let is_journal = args.journal_path.is_some();
+    let mut watcher = match args.watch {
+        true => args.journal_path.as_ref().map(|path| JournalWatcher::new(path)),
+        false => None,
+    };
+
+    // prompts are answered on another thread while we watch the journal
+    let input = watcher.as_ref().map(|_| user_input::spawn_line_reader());
 
-    let search_cache = SearchCache::new();
+    let eddn = args.eddn.as_ref().map(|source| EddnListener::open(source));
+
+    let search_cache = SearchCache::with_exclusions(args.exclusions.clone());
+    let mut exclusions = args.exclusions.clone();
+
+    // set when the player would rather keep their cargo aboard
+    let mut keep_hold = false;
 
     let mut quit = false;
     'route: while !quit {
+        if let Some(ref eddn) = eddn {
+            let mut updated_stations = 0;
+            for message in eddn.drain() {
+                if let Some((station_id, changed)) = universe.apply_eddn_message(&message) {
+                    if changed > 0 {
+                        search_cache.invalidate_station(station_id);
+                        updated_stations += 1;
+                    }
+                }
+            }
+
+            if updated_stations > 0 {
+                println!("eddn:\tupdated prices at {} stations", updated_stations);
+            }
+        }
+
         println!("wait:\tcalculating ...");
 
         let universe_snapshot = universe.snapshot();
 
+        if !keep_hold && !player_state.held_cargo.is_empty() {
+            let query = HoldSaleQuery {
+                within: hold_range,
+                limit: HOLD_SALE_LIMIT,
+            };
+
+            match query.plan(&universe_snapshot, &player_state, search_quality, &search_cache) {
+                Some(sale) => {
+                    println!("{}", SEPARATOR);
+                    let accepted = run_hold_sale_hop(&sale, i, &mut watcher, &input);
+                    println!("{}", SEPARATOR);
+
+                    match accepted {
+                        Some(true) => {
+                            player_state = player_state.with_hold_sale(&sale);
+                            i += 1;
+                        }
+                        Some(false) => keep_hold = true,
+                        None => break 'route,
+                    }
+
+                    continue 'route;
+                }
+                None => {
+                    println!(
+                        "hold:\tno station within {:.0} ly buys your {} tons of cargo.  \
+                         routes will use the {} tons free",
+                        query.within,
+                        player_state.held_tons(),
+                        player_state.free_cargo()
+                    );
+                    keep_hold = true;
+                }
+            }
+        }
+
         let mut search = SearchStation::new(player_state.clone(), search_quality);
         let mut results = search.next_trades(&universe_snapshot, &search_cache);
 
         let mut accepted_trade = None;
+        let mut trade_started_at = None;
 
         'trade: for result in results.drain(..) {
             let trade = result.trade.clone();
@@ -394,9 +951,12 @@ fn run_search(
             println!("");
 
             println!(
-                "buy:\t{} [{}]",
-                trade.unit.buy_system.system_name, trade.unit.buy_station.station_name,
+                "buy:\t{} [{}]{}",
+                trade.unit.buy_system.system_name,
+                trade.unit.buy_station.station_name,
+                station_label(trade.unit.buy_station),
             );
+            print_station_details(trade.unit.buy_system, trade.unit.buy_station);
 
             println!(
                 "\t{} [{}] at {} x {}",
@@ -415,17 +975,31 @@ fn run_search(
 
             println!("");
 
+            let legality_note = match (trade.unit.is_smuggled, trade.unit.is_prohibited) {
+                (true, _) => {
+                    let jurisdiction = legality::describe_jurisdiction(
+                        trade.unit.sell_system,
+                        trade.unit.sell_station,
+                    );
+
+                    match jurisdiction.is_empty() {
+                        true => ", Black Market - Illegal Cargo!".to_string(),
+                        false => format!(", Black Market - Illegal Cargo ({})!", jurisdiction),
+                    }
+                }
+                (false, true) => ", Illegal Cargo!".to_string(),
+                (false, false) => "".to_string(),
+            };
+
             println!(
-                "sell:\t{} [{}] at {}{}",
+                "sell:\t{} [{}]{} at {}{}",
                 trade.unit.sell_system.system_name,
                 trade.unit.sell_station.station_name,
+                station_label(trade.unit.sell_station),
                 NumericUnit::new_string(trade.unit.sell.sell_price, &"cr".to_string()),
-                if trade.unit.is_prohibited {
-                    ", Illegal Cargo!"
-                } else {
-                    ""
-                }
+                legality_note
             );
+            print_station_details(trade.unit.sell_system, trade.unit.sell_station);
 
             println!(
                 "\t{} profit for balance {}",
@@ -433,6 +1007,20 @@ fn run_search(
                 NumericUnit::new_string(trade_state.credit_balance, &"cr".to_string())
             );
 
+            if trade.risk_total > 0 {
+                println!(
+                    "\t{} expected in fines and seized cargo",
+                    NumericUnit::new_string(trade.risk_total, &"cr".to_string())
+                );
+            }
+
+            if trade.shortfall_total > 0 {
+                println!(
+                    "\t{} expected to be lost to market states",
+                    NumericUnit::new_string(trade.shortfall_total, &"cr".to_string())
+                );
+            }
+
             println!("");
             println!(
                 "expect:\t{} profit/min from trade over {:.1} mins",
@@ -458,18 +1046,85 @@ fn run_search(
                 trade.unit.adjusted_time.time_to_station / 60f64
             );
 
-            if args.auto_accept {
+            if let Some(ref mut watcher) = watcher {
+                println!("");
+                println!(
+                    "start:\twaiting for a purchase at {} ...",
+                    trade.unit.buy_station.station_name
+                );
+
+                println!("\tn) for new trade");
+                println!(
+                    "\tx) never suggest {} [{}] again",
+                    trade.unit.sell_system.system_name, trade.unit.sell_station.station_name
+                );
+                println!("\tq) to quit");
+
+                let input = input.as_ref().unwrap();
+                let event = loop {
+                    let event = watcher.wait_for_or_input(input, |event| match event {
+                        &JournalEvent::MarketBuy(ref buy) => {
+                            let is_trade = is_trade_purchase(buy, &trade);
+                            if !is_trade {
+                                println!(
+                                    "\tpurchased {}, but the trade is {} at {}",
+                                    buy.commodity,
+                                    trade.unit.commodity_name,
+                                    trade.unit.buy_station.station_name
+                                );
+                            }
+
+                            is_trade
+                        }
+                        _ => false,
+                    });
+
+                    match event {
+                        WatchEvent::Journal(event) => break event,
+                        WatchEvent::Input(line) => match &line[..] {
+                            "n" | "new" => {
+                                println!("{}", SEPARATOR);
+                                continue 'trade;
+                            }
+                            "x" | "exclude" => {
+                                exclude_sell_station(
+                                    &trade,
+                                    universe,
+                                    &mut exclusions,
+                                    &search_cache,
+                                );
+                                println!("{}", SEPARATOR);
+                                continue 'route;
+                            }
+                            "q" | "quit" => {
+                                quit = true;
+                                break 'route;
+                            }
+                            _ => println!("\tbuy the trade commodity, or enter n, x or q"),
+                        },
+                    }
+                };
+
+                trade_started_at = event.unix_seconds();
+                accepted_trade = Some(trade);
+                println!("");
+                break 'trade;
+            } else if args.auto_accept {
                 accepted_trade = Some(trade);
                 println!("");
                 break 'trade;
             } else {
                 println!("");
                 println!("start:\tenter) to accept trade");
-                if !is_edce {
+                if !is_journal {
                     println!("\tu) to update buy price ({})", trade.unit.buy_price);
                 }
 
                 println!("\tn) for new trade");
+                println!(
+                    "\tx) never suggest {} [{}] again",
+                    trade.unit.sell_system.system_name, trade.unit.sell_station.station_name
+                );
                 println!("\tq) to quit");
                 // the first trade is from the station the user is docked at
                 // so calculate it automatically
@@ -492,6 +1147,11 @@ fn run_search(
                         println!("{}", SEPARATOR);
                         continue;
                     }
+                    "x" | "exclude" => {
+                        exclude_sell_station(&trade, universe, &mut exclusions, &search_cache);
+                        println!("{}", SEPARATOR);
+                        continue 'route;
+                    }
                     "q" | "quit" => {
                         // it's technically not needed to set this,
                         // but just in case the code changes in the future,
@@ -518,18 +1178,72 @@ fn run_search(
         let trade_snapshot = trade.clone();
 
         let start_time = PreciseTime::now();
-        println!("end:\tenter) to complete trade");
-        if !is_edce {
-            println!("\tu) to update sell price ({})", trade.unit.sell_price);
-        }
-        println!("\tq) to complete route");
+        let mut journal_seconds = None;
 
-        // the first trade is from the station the user is docked at
-        // so calculate it automatically
-        let str = user_input::read_line();
+        let str = match watcher {
+            Some(ref mut watcher) => {
+                println!(
+                    "end:\twaiting for a sale at {} [{}] ...",
+                    trade.unit.sell_system.system_name, trade.unit.sell_station.station_name
+                );
 
-        if let Some(edce_data) = EdceData::generate_opt(&args.edce_path) {
-            if let Some(price_update) = edce_data.apply_edce_adjustments(universe) {
+                let event = watcher.wait_for(|event| match event {
+                    &JournalEvent::Undocked(ref e) => {
+                        println!("\tundocked from {}", e.station_name);
+                        false
+                    }
+                    &JournalEvent::FsdJump(ref e) => {
+                        println!("\tjumped to {}", e.star_system);
+                        false
+                    }
+                    &JournalEvent::Docked(ref e) => {
+                        println!("\tdocked at {} [{}]", e.star_system, e.station_name);
+                        false
+                    }
+                    &JournalEvent::MarketSell(ref sell) => {
+                        let is_trade = is_trade_sale(sell, &trade);
+                        if !is_trade {
+                            println!(
+                                "\tsold {}, but the trade is {} at {}",
+                                sell.commodity,
+                                trade.unit.commodity_name,
+                                trade.unit.sell_station.station_name
+                            );
+                        }
+
+                        is_trade
+                    }
+                    _ => false,
+                });
+
+                // the trip time comes from the event timestamps, not from the time we noticed them
+                journal_seconds = match (trade_started_at, event.unix_seconds()) {
+                    (Some(start), Some(end)) if end > start => Some((end - start) as f64),
+                    _ => None,
+                };
+
+                if let JournalEvent::MarketSell(ref sell) = event {
+                    trade = trade.with_sell_price(sell.sell_price);
+                }
+
+                println!("");
+                String::new()
+            }
+            None => {
+                println!("end:\tenter) to complete trade");
+                if !is_journal {
+                    println!("\tu) to update sell price ({})", trade.unit.sell_price);
+                }
+                println!("\tq) to complete route");
+
+                // the first trade is from the station the user is docked at
+                // so calculate it automatically
+                user_input::read_line()
+            }
+        };
+
+        if let Some(journal_data) = JournalData::generate_opt(&args.journal_path) {
+            if let Some(price_update) = journal_data.apply_market_adjustments(universe) {
                 if price_update.station.station_id == trade.unit.sell_station.station_id {
                     let updated_active = price_update
                         .changes
@@ -540,22 +1254,25 @@ fn run_search(
 
                     if updated_active {
                         println!(
-                            "edce:\tupdated trade commodity and {} others",
+                            "journal:\tupdated trade commodity and {} others",
                             std::cmp::max(price_update.changes.len() - 1, 0)
                         );
                     } else {
-                        println!("edce:\tupdated {} commodities", price_update.changes.len());
+                        println!(
+                            "journal:\tupdated {} commodities",
+                            price_update.changes.len()
+                        );
                     }
                     println!("");
                 } else if price_update.station.station_id == trade.unit.buy_station.station_id {
                     println!(
-                        "edce:\tdata received was for original buy station - {}",
+                        "journal:\tmarket data was for original buy station - {}",
                         price_update.station.station_name
                     );
-                    println!("\tplease wait a few seconds after docking before completing trade");
-                } else if price_update.station.station_id == trade.unit.buy_station.station_id {
+                    println!("\tplease open the commodity market before completing trade");
+                } else {
                     println!(
-                        "edce:\tdata received was for unknown station - {}",
+                        "journal:\tmarket data was for unknown station - {}",
                         price_update.station.station_name
                     );
                     println!("\tyou appear to have docked at the wrong station!");
@@ -583,8 +1300,8 @@ fn run_search(
         universe.apply_trade(&trade, &search_cache);
 
         let span = start_time.to(PreciseTime::now());
-        let seconds = span.num_milliseconds() as f64 / 1000f64;
-        let minutes = span.num_milliseconds() as f64 / 60000f64;
+        let seconds = journal_seconds.unwrap_or(span.num_milliseconds() as f64 / 1000f64);
+        let minutes = seconds / 60f64;
 
         let profit_per_min = trade.profit_total as f64 / minutes;
 
@@ -606,7 +1323,12 @@ fn run_search(
             minutes
         );
 
-        match TimeAdjustment::new(&trade, seconds) {
+        let time_adjustment = match journal_seconds {
+            Some(journal_seconds) => Some(TimeAdjustment::from_journal(&trade, journal_seconds)),
+            None => TimeAdjustment::new(&trade, seconds),
+        };
+
+        match time_adjustment {
             Some(adjustment) => {
                 adjustment.save();
                 universe.apply_time_adjustment(adjustment.clone());
@@ -648,3 +1370,55 @@ fn run_search(
     // print overall stats
     // save price updates
 }
+
+// e.g. 'Industrial, Kumo Crew (Boom), High security, refuel/repair/rearm'
+fn print_station_details(system: &System, station: &Station) {
+    let mut details = Vec::new();
+
+    if !station.economies.is_empty() {
+        details.push(station.economies.join("/"));
+    }
+
+    let states: Vec<&String> = station.states.iter().chain(system.states.iter()).collect();
+    let faction = station.faction.as_ref().or(system.controlling_faction.as_ref());
+    match (faction, states.is_empty()) {
+        (Some(faction), true) => details.push(faction.clone()),
+        (Some(faction), false) => {
+            details.push(format!("{} ({})", faction, join_states(&states)))
+        }
+        (None, false) => details.push(join_states(&states)),
+        (None, true) => {}
+    }
+
+    if let Some(ref security) = system.security {
+        details.push(format!("{} security", security));
+    }
+
+    let services = station.services();
+    if !services.is_empty() {
+        details.push(services.join("/"));
+    }
+
+    if !details.is_empty() {
+        println!("\t{}", details.join(", "));
+    }
+}
+
+fn join_states(states: &Vec<&String>) -> String {
+    let mut unique: Vec<&str> = Vec::new();
+    for state in states {
+        if !unique.contains(&state.as_str()) {
+            unique.push(state.as_str());
+        }
+    }
+
+    unique.join(", ")
+}
+
+fn station_label(station: &Station) -> &'static str {
+    match (station.is_fleet_carrier, station.is_planetary) {
+        (true, _) => " (fleet carrier)",
+        (false, true) => " (planetary)",
+        (false, false) => "",
+    }
+}
@@ -7,14 +7,21 @@ It is useful to set the -c Credit, -r Jump Range, -p Ship Size, and \n\
 The tool will interactively prompt you for any remaining information,\n\
 such as the Current Station and Credit Balance. \n\
 \n\
-Alternatively, you can provide the --edce parameter with the path to an \n\
-installed copy of Elite Dangerous Companion Emulator. With EDCE integration, 
-EliteTrader can automatically load your player state at startup, and\n\
-commodity prices during a trade.  It is useful to set the --autoaccept\n\
-parameter when EDCE is configured, as you never need to fix a buy price.\n\
-Note: you need python 3, and the Requests library (pip install requests)\n\
-installed, and must configure the client outside of Elite Trader.  \n\
-See: https://github.com/Andargor/edce-client for setup instructions.\n\
+Alternatively, you can provide the --journal parameter, optionally with\n\
+the path to your Elite Dangerous Player Journal directory.  With Journal \n\
+integration, EliteTrader can automatically load your player state at startup,\n\
+and commodity prices during a trade.  It is useful to set the --autoaccept\n\
+parameter when the journal is configured, as you never need to fix a buy price.\n\
+Open the commodity market after docking, so the game writes Market.json.\n\
+With --watch, the buy and sell prompts complete themselves when the\n\
+journal records your purchase and sale, and trip times are measured\n\
+from the journal timestamps.\n\
+\n\
+Market data is imported from the dump given by --source, or the \"source\"\n\
+setting in ~/.elite_trader/config.json.  The source can be a directory of\n\
+eddb-style dumps, or a spansh galaxy_populated.json file.\n\
+Planetary stations are only suggested with --planetary, and illegal\n\
+goods are only sold on black markets with --smuggling.\n\
 \n\
 Set Minimum Balance argument carefully.  You should allow your rebuy\n\
 cost plus a full load of expensive cargo, or two of each to be safe.\n\
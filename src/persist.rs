@@ -1,9 +1,10 @@
-use csv::{self, Reader};
+use reqwest::blocking::Response;
 
 use std::fs::create_dir_all;
 use std::fs::File;
+use std::io::BufReader;
 use std::io::Read;
-use std::io::{Cursor, Write};
+use std::io::Write;
 use std::path::Path;
 use std::path::PathBuf;
 
@@ -12,6 +13,8 @@ use std::env::home_dir;
 use serde::de::DeserializeOwned;
 use serde::Serialize;
 
+use crate::util::stream;
+
 pub fn get_base_directory() -> PathBuf {
     match home_dir() {
         Some(home_path) => {
@@ -42,6 +45,44 @@ pub fn read_json<T: DeserializeOwned>(path: &Path) -> T {
     }
 }
 
+pub fn read_csv<T>(path: &Path) -> Vec<T>
+where
+    T: DeserializeOwned + Send,
+{
+    let file = open_file(path);
+    let name = path.to_str().unwrap_or("<unknown>");
+
+    match stream::read_csv(BufReader::new(file)) {
+        Ok(result) => result.report(name),
+        Err(reason) => panic!("Failed to parse file {}, reason: {}", name, reason),
+    }
+}
+
+// reads a file containing a large JSON array, skipping elements which don't deserialize
+pub fn read_json_array<T>(path: &Path) -> Vec<T>
+where
+    T: DeserializeOwned + Send,
+{
+    let file = open_file(path);
+    let name = path.to_str().unwrap_or("<unknown>");
+
+    match stream::read_json_array(BufReader::new(file)) {
+        Ok(result) => result.report(name),
+        Err(reason) => panic!("Failed to parse file {}, reason: {}", name, reason),
+    }
+}
+
+fn open_file(path: &Path) -> File {
+    match File::open(path) {
+        Ok(f) => f,
+        Err(reason) => panic!(
+            "Failed to open file {}, reason: {}",
+            path.to_str().unwrap(),
+            reason
+        ),
+    }
+}
+
 pub fn read_text_from_file(file: &mut File) -> String {
     let mut s = String::new();
     file.read_to_string(&mut s).unwrap();
@@ -88,21 +129,23 @@ where
     };
 }
 
-fn http_read(url: &String) -> String {
+fn http_open(url: &String) -> Response {
     let client = reqwest::blocking::Client::new();
     let resp = client
         .get(url)
         .header("Accept-Encoding", "gzip, deflate, sdch")
-        .send()
-        .unwrap();
+        .send();
 
-    resp.text().unwrap()
+    match resp.and_then(|r| r.error_for_status()) {
+        Ok(r) => r,
+        Err(reason) => panic!("Failed to request URL {}, reason: {}", url, reason),
+    }
 }
 
 pub fn http_read_json<T: DeserializeOwned>(url: &String) -> T {
-    let body = http_read(url);
+    let resp = http_open(url);
 
-    match serde_json::from_str(&body) {
+    match serde_json::from_reader(BufReader::new(resp)) {
         Ok(result) => result,
         Err(reason) => panic!(
             "Failed to parse response from URL {}, reason: {}",
@@ -111,13 +154,32 @@ pub fn http_read_json<T: DeserializeOwned>(url: &String) -> T {
     }
 }
 
-pub fn http_read_csv<T>(url: &String) -> Vec<T>
+pub fn http_read_json_array<T>(url: &String) -> Vec<T>
 where
-    T: DeserializeOwned,
+    T: DeserializeOwned + Send,
 {
-    let body = http_read(url);
+    let resp = http_open(url);
 
-    let mut rdr = Reader::from_reader(Cursor::new(body));
+    match stream::read_json_array(BufReader::new(resp)) {
+        Ok(result) => result.report(url),
+        Err(reason) => panic!(
+            "Failed to parse response from URL {}, reason: {}",
+            url, reason
+        ),
+    }
+}
 
-    rdr.deserialize().map(|e| e.unwrap()).collect::<Vec<T>>()
+pub fn http_read_csv<T>(url: &String) -> Vec<T>
+where
+    T: DeserializeOwned + Send,
+{
+    let resp = http_open(url);
+
+    match stream::read_csv(BufReader::new(resp)) {
+        Ok(result) => result.report(url),
+        Err(reason) => panic!(
+            "Failed to parse response from URL {}, reason: {}",
+            url, reason
+        ),
+    }
 }
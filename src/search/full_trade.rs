@@ -9,6 +9,10 @@ use crate::search::unit_trade::UnitTrade;
 pub struct FullTrade<'a> {
     pub unit: UnitTrade<'a>,
     pub profit_total: u32,
+    // the part of the profit we expect to lose to fines, if the cargo is smuggled
+    pub risk_total: u32,
+    // the part of the rest we don't expect to see, if a market changes before we get there
+    pub shortfall_total: u32,
     pub used_cargo: u32,
     pub profit_per_min: f64,
     pub is_valid: bool,
@@ -38,9 +42,16 @@ impl<'a> FullTrade<'a> {
         //		println!( "Using {} of {}, profit/ton {}, profit total {}, profit/min {} over {}sec",
         //			used_cargo, buy.commodity.commodity_name,
         //			profit_per_ton, profit_total, profit_per_min.unwrap_or(0f64), cost_in_seconds );
+        let profit_total = unit.profit_per_ton * used_cargo;
+        let risk_total = (profit_total as f64 * unit.risk) as u32;
+        let shortfall_total =
+            ((profit_total - risk_total) as f64 * (1f64 - unit.market_factor())) as u32;
+
         FullTrade {
             used_cargo: used_cargo,
-            profit_total: unit.profit_per_ton * used_cargo,
+            profit_total: profit_total,
+            risk_total: risk_total,
+            shortfall_total: shortfall_total,
             profit_per_min: profit_per_min,
             is_valid: is_valid,
             is_cyclic: is_cyclic,
@@ -63,6 +74,11 @@ impl<'a> FullTrade<'a> {
         FullTrade::new(&self.state, new_unit)
     }
 
+    // the profit we expect, after fines and market states
+    pub fn expected_total(&self) -> u32 {
+        self.profit_total - self.risk_total - self.shortfall_total
+    }
+
     pub fn max_runs(&self) -> f64 {
         self.unit.buy.supply as f64 / self.used_cargo as f64
     }
@@ -79,6 +95,7 @@ impl<'a> FullTrade<'a> {
         }
 
         let possible_cargo = (state.credit_balance - state.minimum_balance) / buy.buy_price as u32;
-        min(min(possible_cargo, state.cargo_capacity), buy.supply)
+        // cargo we're still carrying takes up room in the hold
+        min(min(possible_cargo, state.free_cargo()), buy.supply)
     }
 }
@@ -0,0 +1,335 @@
+use std::collections::HashMap;
+
+use crate::data::market_state;
+use crate::data::trader::*;
+use crate::data::Universe;
+use crate::search::options::SellOptions;
+use crate::search::player_state::HeldCargo;
+use crate::search::player_state::PlayerState;
+use crate::search::search::SearchStation;
+use crate::search::search_cache::SearchCache;
+use crate::search::search_quality::SearchQuality;
+use crate::search::time_estimate::TimeEstimate;
+use crate::search::unit_trade::UnitTrade;
+
+// finds the best place to sell the cargo we already have, after mining or an abandoned route.
+// there is no buy, so stations are ranked by what the hold is worth there, per minute of travel.
+//
+// a station doesn't need to take everything - cargo it doesn't buy is listed as unsold,
+// and can be sold with another query from there
+
+// the route planner looks past the sale at this many of the best stations.
+// the best place to sell isn't always the best place to start trading from
+const PLAN_CANDIDATES: usize = 3;
+
+pub struct HoldSaleQuery {
+    // in light years from the player's system
+    pub within: f64,
+    pub limit: usize,
+}
+
+pub struct HoldSale<'a> {
+    pub system: &'a System,
+    pub station: &'a Station,
+    // the best listing at the station for each held commodity, and the tons sold to it
+    pub sales: Vec<(&'a Listing, u32)>,
+    pub unsold: Vec<HeldCargo>,
+    // the listed value of the sales
+    pub credits_total: u32,
+    // the value we expect when we arrive, less smuggling fines and given the market states
+    pub credits_expected: f64,
+    pub time: TimeEstimate,
+}
+
+impl<'a> HoldSale<'a> {
+    pub fn credits_per_min(&self) -> f64 {
+        match self.time.time_total {
+            0f64 => 60f64 * self.credits_expected,
+            t => 60f64 * self.credits_expected / t,
+        }
+    }
+}
+
+impl HoldSaleQuery {
+    pub fn run<'a>(&self, universe: &'a Universe, state: &PlayerState) -> Vec<HoldSale<'a>> {
+        if state.held_cargo.is_empty() {
+            return Vec::new();
+        }
+
+        let system = state.get_system(universe);
+        let station = state.get_station(universe);
+        let systems = universe.get_systems_in_range(system, self.within);
+
+        let mut by_station: HashMap<u32, Vec<&'a Listing>> = HashMap::new();
+        for listing in SellOptions::sells_from_systems(systems).nodes {
+            let is_held = state
+                .held_cargo
+                .iter()
+                .any(|held| held.commodity.commodity_id == listing.commodity.commodity_id);
+
+            if is_held {
+                by_station.entry(listing.station_id).or_default().push(listing);
+            }
+        }
+
+        let mut results: Vec<HoldSale<'a>> = by_station
+            .into_iter()
+            .filter_map(|(station_id, listings)| {
+                let target_station = universe.get_station(station_id)?;
+                let target_system = universe.get_system(target_station.system_id)?;
+
+                if market_state::is_closed(target_system, target_station) {
+                    return None;
+                }
+
+                HoldSaleQuery::sale(
+                    state,
+                    system,
+                    station,
+                    target_system,
+                    target_station,
+                    listings,
+                )
+            })
+            .collect();
+
+        results.sort_by(|a, b| {
+            b.credits_per_min()
+                .partial_cmp(&a.credits_per_min())
+                .unwrap_or(std::cmp::Ordering::Equal)
+        });
+        results.truncate(self.limit);
+
+        results
+    }
+
+    // the first hop of a route which starts with cargo aboard - sell what we can, then buy.
+    // candidates are scored by the sale and the best route from the station, per minute
+    pub fn plan<'a>(
+        &self,
+        universe: &'a Universe,
+        state: &PlayerState,
+        quality: SearchQuality,
+        cache: &SearchCache,
+    ) -> Option<HoldSale<'a>> {
+        let query = HoldSaleQuery {
+            within: self.within,
+            limit: PLAN_CANDIDATES,
+        };
+
+        let mut best: Option<(f64, HoldSale<'a>)> = None;
+        for sale in query.run(universe, state) {
+            let mut credits = sale.credits_expected;
+            let mut time = sale.time.time_total;
+
+            let mut search = SearchStation::new(state.with_hold_sale(&sale), quality);
+            if let Some(route) = search.next_trades(universe, cache).first() {
+                credits += route.expected_total() as f64;
+                time += route.time_total;
+            }
+
+            let score = credits / time.max(1f64);
+            match best {
+                Some((best_score, _)) if best_score >= score => {}
+                _ => best = Some((score, sale)),
+            }
+        }
+
+        best.map(|e| e.1)
+    }
+
+    fn sale<'a>(
+        state: &PlayerState,
+        system: &System,
+        station: &Station,
+        target_system: &'a System,
+        target_station: &'a Station,
+        listings: Vec<&'a Listing>,
+    ) -> Option<HoldSale<'a>> {
+        let mut sales = Vec::new();
+        let mut unsold = Vec::new();
+        let mut credits_total = 0u32;
+        let mut credits_expected = 0f64;
+
+        for held in &state.held_cargo {
+            // a station can list a commodity twice - on the market, and on the black market
+            let best = listings
+                .iter()
+                .filter(|e| e.commodity.commodity_id == held.commodity.commodity_id)
+                .filter(|e| {
+                    UnitTrade::is_allowed(state, &e.commodity, e, target_system, target_station)
+                })
+                .map(|e| (*e, HoldSaleQuery::unit_value(state, e)))
+                .max_by(|a, b| a.1.partial_cmp(&b.1).unwrap_or(std::cmp::Ordering::Equal));
+
+            match best {
+                Some((listing, value)) => {
+                    credits_total += listing.sell_price * held.tons;
+                    credits_expected += value * held.tons as f64;
+                    sales.push((listing, held.tons));
+                }
+                None => unsold.push(held.clone()),
+            }
+        }
+
+        if sales.is_empty() {
+            return None;
+        }
+
+        let (reliability, price_shift) = market_state::outlook(target_system, target_station);
+
+        Some(HoldSale {
+            system: target_system,
+            station: target_station,
+            sales: sales,
+            unsold: unsold,
+            credits_total: credits_total,
+            credits_expected: credits_expected * reliability * (1f64 + price_shift),
+            time: TimeEstimate::new_adjusted_estimate(
+                state,
+                system,
+                station,
+                target_system,
+                target_station,
+            ),
+        })
+    }
+
+    // the sell price of one ton, less the fines we expect if it is smuggled
+    fn unit_value(state: &PlayerState, listing: &Listing) -> f64 {
+        let risk = match listing.is_black_market {
+            true => state.smuggling_risk.unwrap_or(0f64),
+            false => 0f64,
+        };
+
+        listing.sell_price as f64 * (1f64 - risk)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::filter::FilterOptions;
+    use crate::data::source::FixtureSource;
+
+    #[test]
+    fn test_sell_hold() {
+        let mut filter = FilterOptions::new(ShipSize::Large);
+        filter.permits = vec![crate::data::filter::Permit::Id(3)];
+        let universe = Universe::from_source(&FixtureSource::sample(), &filter);
+
+        let giger = universe.get_station(10).unwrap();
+        let gold = giger.listings[0].commodity.clone();
+        let explosives = giger.listings[1].commodity.clone();
+
+        let mut state = PlayerState::sample(&universe, 10);
+
+        let query = HoldSaleQuery {
+            within: 50f64,
+            limit: 10,
+        };
+        assert!(query.run(&universe, &state).is_empty());
+
+        state.held_cargo = vec![
+            HeldCargo {
+                commodity: gold,
+                tons: 50,
+            },
+            HeldCargo {
+                commodity: explosives,
+                tons: 10,
+            },
+        ];
+
+        let results = query.run(&universe, &state);
+        let ids: Vec<u32> = results.iter().map(|e| e.station.station_id).collect();
+        assert_eq!(3, ids.len());
+        assert!(ids.contains(&30));
+        assert!(results
+            .windows(2)
+            .all(|e| e[0].credits_per_min() >= e[1].credits_per_min()));
+
+        // Siddha Ring won't take the explosives
+        let siddha = results.iter().find(|e| e.station.station_id == 20).unwrap();
+        assert_eq!(1, siddha.sales.len());
+        assert_eq!(500000, siddha.credits_total);
+        assert_eq!(1, siddha.unsold.len());
+        assert_eq!(10, siddha.unsold[0].tons);
+
+        let giger = results.iter().find(|e| e.station.station_id == 10).unwrap();
+        assert_eq!(2, giger.sales.len());
+        assert_eq!(50 * 8800 + 10 * 180, giger.credits_total);
+    }
+
+    #[test]
+    fn test_plan_with_cargo_aboard() {
+        let universe =
+            Universe::from_source(&FixtureSource::sample(), &FilterOptions::new(ShipSize::Large));
+
+        let giger = universe.get_station(10).unwrap();
+        let gold = &giger.listings[0];
+        let explosives = giger.listings[1].commodity.clone();
+
+        // Siddha Ring prohibits explosives, so they can only be sold at Giger Hub
+        let mut state = PlayerState::sample(&universe, 20);
+        state.held_cargo = vec![HeldCargo {
+            commodity: explosives,
+            tons: 10,
+        }];
+
+        // the explosives and a few limpets leave 85 tons for trading
+        state.reserved_tons = 5;
+        assert_eq!(85, crate::search::FullTrade::used_cargo(&state, gold));
+
+        let query = HoldSaleQuery {
+            within: 50f64,
+            limit: 10,
+        };
+        let sale = query
+            .plan(&universe, &state, SearchQuality::Medium, &SearchCache::new())
+            .expect("Giger Hub buys explosives");
+        assert_eq!(10, sale.station.station_id);
+
+        let state = state.with_hold_sale(&sale);
+        assert_eq!(10, state.station_id);
+        assert_eq!(1000000 + 10 * 180, state.credit_balance);
+        assert!(state.held_cargo.is_empty());
+        assert_eq!(95, crate::search::FullTrade::used_cargo(&state, gold));
+    }
+
+    #[test]
+    fn test_sell_hold_on_black_market() {
+        let mut source = FixtureSource::sample();
+        for station in source.stations.iter_mut().filter(|e| e.id == 20) {
+            station.has_blackmarket = Some(true);
+        }
+
+        let mut filter = FilterOptions::new(ShipSize::Large);
+        filter.smuggling = true;
+        let universe = Universe::from_source(&source, &filter);
+
+        let explosives = universe.get_station(10).unwrap().listings[1].commodity.clone();
+        let mut state = PlayerState::sample(&universe, 10);
+        state.smuggling_risk = Some(0.25);
+        state.held_cargo = vec![HeldCargo {
+            commodity: explosives,
+            tons: 10,
+        }];
+
+        let query = HoldSaleQuery {
+            within: 50f64,
+            limit: 10,
+        };
+        let results = query.run(&universe, &state);
+        let siddha = results.iter().find(|e| e.station.station_id == 20).unwrap();
+        assert!(siddha.sales[0].0.is_black_market);
+
+        // the listed value is credited, and the fines only lower the expectation
+        assert_eq!(10 * 290, siddha.credits_total);
+        assert_eq!(10f64 * 290f64 * 0.75, siddha.credits_expected);
+        assert_eq!(
+            1000000 + 10 * 290,
+            state.with_hold_sale(siddha).credit_balance
+        );
+    }
+}
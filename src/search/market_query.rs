@@ -0,0 +1,148 @@
+use crate::data::trader::*;
+use crate::data::Universe;
+use crate::search::options::BuyOptions;
+use crate::search::options::SellOptions;
+use crate::search::player_state::PlayerState;
+use crate::search::time_estimate::TimeEstimate;
+use crate::search::unit_trade::UnitTrade;
+
+// finds the nearest markets for one commodity, for sourcing goods outside of trade routes.
+// 'nearest' is the estimated trip time from the player's station, not the distance -
+// a station 2ly away and 80kls from the star is usually further than one a few jumps out.
+//
+// only destinations are searched, so excluded stations never show up
+
+#[derive(Clone, Debug)]
+pub enum MarketQueryKind {
+    // stations which sell the commodity to the player, with at least this much supply
+    Supply(u32),
+    // stations which buy the commodity from the player, for at least this price
+    Demand(u32),
+}
+
+pub struct MarketQuery {
+    pub commodity: Commodity,
+    pub kind: MarketQueryKind,
+    // in light years from the player's system
+    pub within: f64,
+    pub limit: usize,
+}
+
+pub struct MarketQueryResult<'a> {
+    pub system: &'a System,
+    pub station: &'a Station,
+    pub listing: &'a Listing,
+    pub time: TimeEstimate,
+}
+
+impl MarketQuery {
+    pub fn run<'a>(
+        &self,
+        universe: &'a Universe,
+        state: &PlayerState,
+    ) -> Vec<MarketQueryResult<'a>> {
+        let system = state.get_system(universe);
+        let station = state.get_station(universe);
+        let systems = universe.get_systems_in_range(system, self.within);
+
+        let options = match self.kind {
+            MarketQueryKind::Supply(_) => BuyOptions::buys_from_systems(systems),
+            MarketQueryKind::Demand(_) => SellOptions::sells_from_systems(systems),
+        };
+
+        let mut results: Vec<MarketQueryResult<'a>> = options
+            .nodes
+            .into_iter()
+            .filter(|e| e.commodity.commodity_id == self.commodity.commodity_id)
+            .filter_map(|listing| {
+                let target_system = universe.get_system(listing.system_id)?;
+                let target_station = universe.get_station(listing.station_id)?;
+
+                let matches = match self.kind {
+                    MarketQueryKind::Supply(min_supply) => listing.supply >= min_supply,
+                    MarketQueryKind::Demand(min_price) => {
+                        listing.sell_price >= min_price
+                            && UnitTrade::is_allowed(
+                                state,
+                                &listing.commodity,
+                                listing,
+                                target_system,
+                                target_station,
+                            )
+                    }
+                };
+
+                if !matches {
+                    return None;
+                }
+
+                Some(MarketQueryResult {
+                    system: target_system,
+                    station: target_station,
+                    listing: listing,
+                    time: TimeEstimate::new_adjusted_estimate(
+                        state,
+                        system,
+                        station,
+                        target_system,
+                        target_station,
+                    ),
+                })
+            })
+            .collect();
+
+        results.sort_by(|a, b| {
+            a.time
+                .time_total
+                .partial_cmp(&b.time.time_total)
+                .unwrap_or(std::cmp::Ordering::Equal)
+        });
+        results.truncate(self.limit);
+
+        results
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::filter::FilterOptions;
+    use crate::data::source::FixtureSource;
+
+    #[test]
+    fn test_nearest_buyers() {
+        let mut filter = FilterOptions::new(ShipSize::Large);
+        filter.permits = vec![crate::data::filter::Permit::Id(3)];
+        let universe = Universe::from_source(&FixtureSource::sample(), &filter);
+        let gold = universe.get_station(10).unwrap().listings[0].commodity.clone();
+
+        let mut query = MarketQuery {
+            commodity: gold,
+            kind: MarketQueryKind::Demand(10000),
+            within: 50f64,
+            limit: 10,
+        };
+
+        // Siddha Ring is one jump closer than the permit port, which pays more
+        let results = query.run(&universe, &PlayerState::sample(&universe, 10));
+        let ids: Vec<u32> = results.iter().map(|e| e.station.station_id).collect();
+        assert_eq!(vec![20, 30], ids);
+        assert!(results[0].time.time_total < results[1].time.time_total);
+
+        query.kind = MarketQueryKind::Demand(11000);
+        let results = query.run(&universe, &PlayerState::sample(&universe, 10));
+        assert_eq!(1, results.len());
+        assert_eq!(30, results[0].station.station_id);
+
+        query.within = 5f64;
+        assert!(query.run(&universe, &PlayerState::sample(&universe, 10)).is_empty());
+
+        // Giger Hub is the only place to buy gold
+        query.kind = MarketQueryKind::Supply(1000);
+        let results = query.run(&universe, &PlayerState::sample(&universe, 20));
+        assert!(results.is_empty());
+        query.within = 50f64;
+        let results = query.run(&universe, &PlayerState::sample(&universe, 20));
+        assert_eq!(10, results[0].station.station_id);
+    }
+}
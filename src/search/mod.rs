@@ -20,5 +20,9 @@ pub use self::full_trade::FullTrade;
 
 pub mod time_estimate;
 
+pub mod market_query;
+
+pub mod hold_sale;
+
 mod player_state;
 pub use self::player_state::PlayerState;
\ No newline at end of file
@@ -48,7 +48,7 @@ impl<'a> BuyOptions<'a> {
         let mut ret = BuyOptions::default();
 
         for system in systems {
-            for station in &*system.stations {
+            for station in system.stations.iter().filter(|e| !e.is_excluded) {
                 for listing in &station.listings {
                     if listing.is_buy() {
                         ret.push(listing);
@@ -63,7 +63,7 @@ impl<'a> BuyOptions<'a> {
     pub fn buys_from_system(system: &System) -> BuyOptions {
         let mut ret = BuyOptions::default();
 
-        for station in &*system.stations {
+        for station in system.stations.iter().filter(|e| !e.is_excluded) {
             for listing in &station.listings {
                 if listing.is_buy() {
                     ret.push(listing);
@@ -74,6 +74,7 @@ impl<'a> BuyOptions<'a> {
         ret
     }
 
+    // the player may be docked at an excluded station, so this one isn't filtered
     pub fn buys_from_station(station: &Station) -> BuyOptions {
         let mut ret = BuyOptions::default();
 
@@ -93,7 +94,7 @@ impl<'a> SellOptions<'a> {
         let mut ret = SellOptions::default();
 
         for system in systems {
-            for station in &*system.stations {
+            for station in system.stations.iter().filter(|e| !e.is_excluded) {
                 for listing in &station.listings {
                     if listing.is_sell() {
                         ret.push(listing);
@@ -108,7 +109,7 @@ impl<'a> SellOptions<'a> {
     pub fn sells_from_system(system: &System) -> SellOptions {
         let mut ret = SellOptions::default();
 
-        for station in &*system.stations {
+        for station in system.stations.iter().filter(|e| !e.is_excluded) {
             for listing in &station.listings {
                 if listing.is_sell() {
                     ret.push(listing);
@@ -1,13 +1,17 @@
 use std::str::FromStr;
 
 use crate::arguments::Arguments;
-use crate::data::trader::{Station, System};
+use crate::data::trader::{commodity_symbol, Commodity, Station, System};
 use crate::data::Universe;
 
 use crate::search::full_trade::FullTrade;
+use crate::search::hold_sale::HoldSale;
 use crate::user_input::*;
 use crate::SEPARATOR;
 
+// the number of close matches offered when a name isn't found
+const SUGGESTION_LIMIT: usize = 8;
+
 #[derive(Clone)]
 pub struct PlayerState {
     pub system_id: u32,
@@ -20,16 +24,46 @@ pub struct PlayerState {
     pub cargo_capacity: u32,
 
     pub raw_adjustment_factor: f64,
+
+    // the fraction of smuggling profit we expect to lose to fines and seized cargo.
+    // None if smuggling is disabled
+    pub smuggling_risk: Option<f64>,
+
+    // cargo already in the hold, e.g. after mining
+    pub held_cargo: Vec<HeldCargo>,
+    // cargo which takes up room, but isn't ours to sell - mission cargo and limpets
+    pub reserved_tons: u32,
+}
+
+#[derive(Clone, Debug)]
+pub struct HeldCargo {
+    pub commodity: Commodity,
+    pub tons: u32,
 }
 //todo: refactor out jump range and cargo capacity.  mutable and immutable properties should be separate.
 #[allow(dead_code)]
 impl PlayerState {
     pub fn new(arguments: &Arguments, universe: &Universe) -> PlayerState {
+        let mut system_name = arguments.system.clone();
         let mut station_name = arguments.station.clone();
         let mut stations;
 
+        if let Some(sys) = system_name.clone() {
+            if universe.get_system_by_name(&sys).is_none() {
+                println!("The system '{}' was not found.", sys);
+                let systems = universe.search_systems_by_name(&sys, SUGGESTION_LIMIT);
+                if !systems.is_empty() {
+                    let names: Vec<&str> = systems.iter().map(|e| &e.system_name[..]).collect();
+                    println!("Similar systems: {}", names.join(", "));
+                }
+
+                println!("Searching stations in all systems instead.");
+                system_name = None;
+            }
+        }
+
         loop {
-            stations = match arguments.system {
+            stations = match system_name {
                 Some(ref sys) => match universe.get_station_by_name(sys, &station_name) {
                     Some(station) => vec![station],
                     None => Vec::new(),
@@ -42,7 +76,32 @@ impl PlayerState {
             }
 
             println!("The station '{}' was not found.", station_name);
-            station_name = prompt_value("t", "corrected station name");
+
+            let suggestions = universe.search_stations_by_name(
+                system_name.as_ref(),
+                &station_name,
+                SUGGESTION_LIMIT,
+            );
+            if suggestions.is_empty() {
+                station_name = prompt_value("t", "corrected station name");
+                continue;
+            }
+
+            println!("{}", SEPARATOR);
+            println!("Did you mean:");
+            print_stations(universe, &suggestions);
+
+            println!("");
+            println!("Please enter the index of your station, or the corrected station name:");
+
+            let line = read_line();
+            match usize::from_str(&line[..]) {
+                Ok(n) if n >= 1 && n <= suggestions.len() => {
+                    stations = vec![suggestions[n - 1]];
+                    break;
+                }
+                _ => station_name = line,
+            }
         }
 
         let station = match stations.len() {
@@ -51,15 +110,7 @@ impl PlayerState {
             _ => {
                 println!("{}", SEPARATOR);
                 println!("Multiple stations were found.");
-                let mut print_index = 1;
-                for station in stations.iter() {
-                    let system = universe.get_system(station.system_id).unwrap();
-                    println!(
-                        "{}) {} [{}]",
-                        print_index, system.system_name, station.station_name
-                    );
-                    print_index += 1;
-                }
+                print_stations(universe, &stations);
 
                 println!("");
                 println!("Please enter the index of your station:");
@@ -87,6 +138,14 @@ impl PlayerState {
             cargo_capacity: arguments.cargo,
 
             raw_adjustment_factor: universe.get_raw_adjustment_factor(),
+
+            smuggling_risk: match arguments.smuggling {
+                true => Some(arguments.smuggling_risk),
+                false => None,
+            },
+
+            held_cargo: held_cargo(universe, &arguments.hold),
+            reserved_tons: arguments.reserved_tons,
         }
     }
 
@@ -109,6 +168,25 @@ impl PlayerState {
         new_state
     }
 
+    // the cargo which can't be sold at the station stays aboard
+    pub fn with_hold_sale(&self, sale: &HoldSale) -> PlayerState {
+        let mut new_state = self.with_station(sale.station);
+        new_state.credit_balance = self.credit_balance + sale.credits_total;
+        new_state.held_cargo = sale.unsold.clone();
+        new_state
+    }
+
+    pub fn held_tons(&self) -> u32 {
+        self.held_cargo.iter().map(|e| e.tons).sum()
+    }
+
+    // the room left for trade cargo
+    pub fn free_cargo(&self) -> u32 {
+        self.cargo_capacity
+            .saturating_sub(self.held_tons())
+            .saturating_sub(self.reserved_tons)
+    }
+
     pub fn get_station<'a>(&self, universe: &'a Universe) -> &'a Station {
         match universe.get_station(self.station_id) {
             Some(v) => v,
@@ -123,3 +201,70 @@ impl PlayerState {
         }
     }
 }
+
+#[cfg(test)]
+impl PlayerState {
+    // docked at the station with an empty hold, 100 tons of room, and a million credits
+    pub fn sample(universe: &Universe, station_id: u32) -> PlayerState {
+        let station = universe.get_station(station_id).unwrap();
+
+        PlayerState {
+            system_id: station.system_id,
+            station_id: station.station_id,
+            credit_balance: 1000000,
+            minimum_balance: 0,
+            jump_range: 20f64,
+            cargo_capacity: 100,
+            raw_adjustment_factor: 1f64,
+            smuggling_risk: None,
+            held_cargo: Vec::new(),
+            reserved_tons: 0,
+        }
+    }
+}
+
+// commodities we don't know are dropped, as they can't be priced anyway
+fn held_cargo(universe: &Universe, hold: &Vec<(String, u32)>) -> Vec<HeldCargo> {
+    let mut held_cargo: Vec<HeldCargo> = Vec::new();
+
+    for (name, tons) in hold {
+        let index = universe.get_index();
+        let commodity = match index.get_commodity_by_symbol(&commodity_symbol(name)) {
+            Some(c) => c,
+            None => {
+                println!(
+                    "The commodity '{}' in your hold was not found, and will be ignored.",
+                    name
+                );
+                let suggestions = index.search_commodities_by_name(name, SUGGESTION_LIMIT);
+                if !suggestions.is_empty() {
+                    let names: Vec<&str> =
+                        suggestions.iter().map(|e| &e.commodity_name[..]).collect();
+                    println!("Similar commodities: {}", names.join(", "));
+                }
+                continue;
+            }
+        };
+
+        match held_cargo
+            .iter_mut()
+            .find(|e| e.commodity.commodity_id == commodity.commodity_id)
+        {
+            Some(held) => held.tons += *tons,
+            None => held_cargo.push(HeldCargo {
+                commodity: commodity.clone(),
+                tons: *tons,
+            }),
+        }
+    }
+
+    held_cargo
+}
+
+// a numbered list, for the player to pick from
+fn print_stations(universe: &Universe, stations: &Vec<&Station>) {
+    for (i, station) in stations.iter().enumerate() {
+        let system = universe.get_system(station.system_id).unwrap();
+        println!("{}) {} [{}]", i + 1, system.system_name, station.station_name);
+    }
+}
@@ -28,17 +28,25 @@ use std::cmp::*;
 pub struct SearchResult<'a> {
     pub trade: FullTrade<'a>,
     pub profit_total: u32,
+    // expected smuggling losses, which are taken out of the score
+    pub risk_total: u32,
+    // expected losses to market states, also taken out of the score
+    pub shortfall_total: u32,
     pub time_total: f64,
 }
 
 impl<'a> SearchResult<'a> {
     pub fn new(trade: FullTrade<'a>) -> SearchResult<'a> {
         let profit_total = trade.profit_total;
+        let risk_total = trade.risk_total;
+        let shortfall_total = trade.shortfall_total;
         let time_total = trade.unit.adjusted_time.time_total;
 
         SearchResult {
             trade: trade,
             profit_total: profit_total,
+            risk_total: risk_total,
+            shortfall_total: shortfall_total,
             time_total: time_total,
         }
     }
@@ -50,6 +58,8 @@ impl<'a> SearchResult<'a> {
         SearchResult {
             trade: trade.clone(),
             profit_total: profit_total,
+            risk_total: self.risk_total + trade.risk_total,
+            shortfall_total: self.shortfall_total + trade.shortfall_total,
             time_total: distance_in_seconds,
         }
     }
@@ -61,6 +71,8 @@ impl<'a> SearchResult<'a> {
         SearchResult {
             trade: self.trade.clone(),
             profit_total: profit_total,
+            risk_total: self.risk_total,
+            shortfall_total: self.shortfall_total,
             time_total: distance_in_seconds,
         }
     }
@@ -72,6 +84,8 @@ impl<'a> SearchResult<'a> {
         SearchResult {
             trade: self.trade.clone(),
             profit_total: profit_total,
+            risk_total: self.risk_total + other.risk_total,
+            shortfall_total: self.shortfall_total + other.shortfall_total,
             time_total: distance_in_seconds,
         }
     }
@@ -83,13 +97,20 @@ impl<'a> SearchResult<'a> {
     pub fn profit_per_min(&self) -> f64 {
         60f64 * self.profit_total as f64 / self.time_total
     }
+
+    // the profit we expect, after fines and market states
+    pub fn expected_total(&self) -> u32 {
+        self.profit_total
+            .saturating_sub(self.risk_total)
+            .saturating_sub(self.shortfall_total)
+    }
 }
 
 impl<'a> Scored<f64> for SearchResult<'a> {
     fn score(&self) -> f64 {
         let val = match self.time_total {
             0f64 => panic!("Cannot score result with 0 distance_in_seconds"),
-            _ => self.profit_total as f64 / self.time_total,
+            _ => self.expected_total() as f64 / self.time_total,
         };
 
         val
@@ -170,6 +191,11 @@ impl<'a> SearchStation {
         let full_trade_1 = search_trade_1.trade;
         let result_1 = SearchResult::new(full_trade_1);
 
+        // a carrier's orders can change before we get there, so don't plan any further
+        if result_1.trade.unit.sell_station.is_fleet_carrier {
+            return Some(result_1);
+        }
+
         match cycle_tracker.find_cycle(&result_1.trade, max_depth - depth) {
             Some(cycle) => return Some(result_1.with_cycle(&cycle)),
             None => {}
@@ -312,3 +338,75 @@ impl<'a> SearchStation {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::filter::FilterOptions;
+    use crate::data::source::FixtureSource;
+
+    #[test]
+    fn test_search_from_excluded_station() {
+        // Giger Hub only has medium pads, so it is excluded for a large ship
+        let mut source = FixtureSource::sample();
+        for station in source.stations.iter_mut().filter(|e| e.id == 10) {
+            station.max_landing_pad_size = Some("M".to_string());
+        }
+
+        let universe = Universe::from_source(&source, &FilterOptions::new(ShipSize::Large));
+        let giger = universe
+            .get_station_by_name(&"Peraesii".to_string(), &"Giger Hub".to_string())
+            .expect("excluded stations should still be found");
+        assert!(giger.is_excluded);
+
+        let state = PlayerState::sample(&universe, giger.station_id);
+        let mut search = SearchStation::new(state, SearchQuality::Medium);
+        let trades = search.next_trades(&universe, &SearchCache::new());
+
+        // Small Outpost pays more, but it can't land a large ship
+        let first = trades.first().expect("the search should start from Giger Hub");
+        assert_eq!(10, first.trade.unit.buy_station.station_id);
+        assert_eq!(20, first.trade.unit.sell_station.station_id);
+        assert!(trades.iter().all(|e| !e.trade.unit.sell_station.is_excluded));
+    }
+
+    #[test]
+    fn test_unsettled_market_ranks_lower() {
+        // Siddha Ring pays more for gold than the Small Outpost, but it is in lockdown
+        let mut source = FixtureSource::sample();
+        for listing in source.listings.iter_mut() {
+            match listing.station_id {
+                20 if listing.commodity_id == 1 => listing.sell_price = 10500,
+                21 => listing.sell_price = 10000,
+                _ => {}
+            }
+        }
+        for station in source.stations.iter_mut().filter(|e| e.id == 20) {
+            station.states = vec![crate::data::eddb::StateJson {
+                name: "Lockdown".to_string(),
+            }];
+        }
+
+        let universe = Universe::from_source(&source, &FilterOptions::new(ShipSize::Small));
+        let state = PlayerState::sample(&universe, 10);
+        let gold = &universe.get_station(10).unwrap().listings[0];
+
+        let result = |station_id: u32| {
+            let sell = &universe.get_station(station_id).unwrap().listings[0];
+            let unit = UnitTrade::new(&universe, &state, gold, sell);
+            SearchResult::new(FullTrade::new(&state, unit))
+        };
+
+        let unsettled = result(20);
+        let calm = result(21);
+        assert!(unsettled.profit_total > calm.profit_total);
+        assert_eq!(0, calm.shortfall_total);
+        assert_eq!(60000, unsettled.shortfall_total);
+        assert!(unsettled.score() < calm.score());
+
+        // and the shortfall is carried along the route
+        let route = calm.with_trade(&unsettled.trade);
+        assert_eq!(60000, route.shortfall_total);
+        assert_eq!(calm.expected_total() + 90000, route.expected_total());
+    }
+}
@@ -3,6 +3,7 @@ use std::sync::RwLock;
 
 use rand::{prelude::SliceRandom, thread_rng};
 
+use crate::data::exclusions::Exclusions;
 use crate::data::trader::*;
 use crate::data::Universe;
 use crate::search::options::*;
@@ -55,6 +56,7 @@ impl CachedTrade {
 pub struct SearchCache {
     trade_cache: RwLock<HashMap<u32, Vec<CachedTrade>>>,
     sell_lookup: RwLock<HashMap<u32, HashSet<u32>>>, //	convergence_filter: VecMap<HashMap<usize, f64>>
+    exclusions: Exclusions,
 }
 
 impl SearchCache {
@@ -74,9 +76,14 @@ impl SearchCache {
     }
 
     pub fn new() -> SearchCache {
+        SearchCache::with_exclusions(Exclusions::default())
+    }
+
+    pub fn with_exclusions(exclusions: Exclusions) -> SearchCache {
         SearchCache {
             trade_cache: RwLock::new(HashMap::new()),
             sell_lookup: RwLock::new(HashMap::new()),
+            exclusions: exclusions,
         }
     }
 
@@ -163,8 +170,20 @@ impl SearchCache {
                 }
             };
 
-            //TODO: allow smuggling
-            if UnitTrade::is_prohibited(&buy.commodity, &sell_station) {
+            let sell_system = match universe.get_system(sell_option.system_id) {
+                Some(system) => system,
+                None => {
+                    continue;
+                }
+            };
+
+            if !UnitTrade::is_allowed(
+                state,
+                &buy.commodity,
+                sell_option,
+                sell_system,
+                sell_station,
+            ) {
                 continue;
             }
 
@@ -184,8 +203,12 @@ impl SearchCache {
         let insert = self.trade_cache.read().unwrap().get(&station_id).is_none();
 
         if insert {
-            let trades =
-                SearchCache::best_1hop_trades(universe, &station.state, station.search_quality);
+            let trades = SearchCache::best_1hop_trades(
+                universe,
+                &station.state,
+                station.search_quality,
+                &self.exclusions,
+            );
             let cache_list = trades
                 .iter()
                 .map(|e| SearchCache::to_cached_trade(e, universe))
@@ -213,6 +236,7 @@ impl SearchCache {
         universe: &'a Universe,
         state: &PlayerState,
         search_quality: SearchQuality,
+        exclusions: &Exclusions,
     ) -> Vec<UnitTrade<'a>> {
         let system = state.get_system(universe);
         let station = state.get_station(universe);
@@ -230,6 +254,10 @@ impl SearchCache {
 
         //		println!("best_trades_in_range - Iterating combinations");
         for buy in BuyOptions::buys_from_station(station).nodes {
+            if exclusions.excludes_commodity(&buy.commodity) {
+                continue;
+            }
+
             let id = buy.commodity.to_id();
             let trades = match sells_by_commodity.get(&id) {
                 Some(t) => t,
@@ -250,8 +278,19 @@ impl SearchCache {
                     }
                 };
 
-                //TODO: allow smuggling
-                if UnitTrade::is_prohibited(&buy.commodity, &sell_station) {
+                let sell_system = match universe.get_system(sell.system_id) {
+                    Some(system) => system,
+                    None => {
+                        continue;
+                    }
+                };
+
+                if !UnitTrade::is_allowed(state, &buy.commodity, sell, sell_system, sell_station) {
+                    continue;
+                }
+
+                // the universe may not have been filtered with these exclusions
+                if exclusions.excludes_station(sell_system, sell_station) {
                     continue;
                 }
 
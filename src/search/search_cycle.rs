@@ -64,7 +64,8 @@ impl SearchCycleElement {
     pub fn new<'a>(trade: &FullTrade<'a>) -> SearchCycleElement {
         SearchCycleElement {
             buy_station_id: trade.unit.buy.station_id,
-            profit_total: trade.profit_total,
+            // repeating a smuggling run, or a run into an unsettled market, repeats the risk
+            profit_total: trade.expected_total(),
             time_total: trade.unit.adjusted_time.time_total,
             is_cyclic: trade.is_cyclic,
         }
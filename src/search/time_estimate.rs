@@ -14,10 +14,18 @@ pub struct TimeEstimate {
     pub time_to_system: f64,
     pub time_total: f64,
 }
-const BUY_TIME: f64 = 36.92425f64;
+pub const BUY_TIME: f64 = 36.92425f64;
 const UNDOCK_TIME: f64 = 77.749256f64;
 const DOCK_TIME: f64 = 56.52650f64;
 const SELL_TIME: f64 = 24.80750f64;
+// surface ports take longer - an orbital glide, then a flight down to the pad,
+// and a climb back out of the gravity well afterwards.
+// these are rough timings from a handful of runs.  the glide and landing are part of the trip to
+// the station, so they are scaled by the player's time adjustments.  the climb out is part of
+// the trip to the system, which isn't adjusted, just like UNDOCK_TIME
+const GLIDE_TIME: f64 = 90f64;
+const SURFACE_DOCK_TIME: f64 = 75f64;
+const SURFACE_UNDOCK_TIME: f64 = 110f64;
 /*
     Big table of magic numbers generated in a fancy Mathemtica environment.
     TLDR: how far you can get on each jump really depends on your jump range
@@ -70,6 +78,7 @@ impl<'a> TimeEstimate {
     pub fn new_normalized_estimate(
         state: &PlayerState,
         buy_system: &'a System,
+        buy_station: &'a Station,
         sell_system: &'a System,
         sell_station: &'a Station,
     ) -> TimeEstimate {
@@ -80,8 +89,10 @@ impl<'a> TimeEstimate {
 
         let jump_count = Self::jump_count(distance_to_system, state.jump_range);
 
-        let time_to_system = Self::raw_time_to_system(distance_to_system, state.jump_range);
-        let time_to_station = Self::raw_time_to_station(distance_to_station as f64);
+        let time_to_system = Self::raw_time_to_system(distance_to_system, state.jump_range)
+            + Self::undock_time(buy_station);
+        let time_to_station = Self::raw_time_to_station(distance_to_station as f64)
+            + Self::dock_time(sell_station);
 
         TimeEstimate {
             distance_to_system: distance_to_system,
@@ -96,6 +107,7 @@ impl<'a> TimeEstimate {
     pub fn new_adjusted_estimate(
         state: &PlayerState,
         buy_system: &'a System,
+        buy_station: &'a Station,
         sell_system: &'a System,
         sell_station: &'a Station,
     ) -> TimeEstimate {
@@ -110,9 +122,10 @@ impl<'a> TimeEstimate {
             distance_to_system,
             state.jump_range,
             state.raw_adjustment_factor,
-        );
+        ) + Self::undock_time(buy_station);
         let time_to_station =
-            Self::adjusted_time_to_station(distance_to_station as f64, state.raw_adjustment_factor);
+            Self::adjusted_time_to_station(distance_to_station as f64, state.raw_adjustment_factor)
+                + state.raw_adjustment_factor * Self::dock_time(sell_station);
 
         TimeEstimate {
             distance_to_system: distance_to_system,
@@ -158,6 +171,22 @@ impl TimeEstimate {
             * (SELL_TIME + DOCK_TIME + Self::supercruise_time(distance_to_station as f64))
     }
 
+    // the extra time to leave a planetary station, on top of UNDOCK_TIME
+    fn undock_time(station: &Station) -> f64 {
+        match station.is_planetary {
+            true => SURFACE_UNDOCK_TIME - UNDOCK_TIME,
+            false => 0f64,
+        }
+    }
+
+    // the extra time to reach the pad at a planetary station, on top of DOCK_TIME
+    fn dock_time(station: &Station) -> f64 {
+        match station.is_planetary {
+            true => GLIDE_TIME + SURFACE_DOCK_TIME - DOCK_TIME,
+            false => 0f64,
+        }
+    }
+
     pub fn jump_count(system_distance: f64, jump_range: f64) -> f64 {
         if system_distance == 0f64 {
             return 0f64;
@@ -168,7 +197,16 @@ impl TimeEstimate {
         }
 
         let index = (4f64 * jump_range) as usize;
-        system_distance / ADJUSTED_JUMP_RANGE[index]
+        let adjusted_range = match ADJUSTED_JUMP_RANGE.get(index) {
+            Some(range) => *range,
+            // past the table, the loss per jump barely changes, so carry on from the last entry
+            None => {
+                let last = ADJUSTED_JUMP_RANGE.len() - 1;
+                ADJUSTED_JUMP_RANGE[last] + jump_range - last as f64 / 4f64
+            }
+        };
+
+        system_distance / adjusted_range
     }
 
     pub fn jump_time(system_distance: f64, jump_range: f64) -> f64 {
@@ -203,3 +241,20 @@ impl TimeEstimate {
         supercruise_time
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_jump_count_long_range() {
+        assert_eq!(0f64, TimeEstimate::jump_count(0f64, 60f64));
+        assert_eq!(1f64, TimeEstimate::jump_count(55f64, 60f64));
+
+        // ranges past the table carry on from its last entry, without a jump at the end
+        let at_end = TimeEstimate::jump_count(500f64, 49.75f64);
+        let past_end = TimeEstimate::jump_count(500f64, 50f64);
+        assert!(past_end < at_end && at_end - past_end < 0.1);
+        assert!(TimeEstimate::jump_count(500f64, 80f64) < 500f64 / 75f64);
+    }
+}
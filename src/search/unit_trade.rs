@@ -2,6 +2,8 @@ use std::fmt::Debug;
 use std::fmt::Error;
 use std::fmt::Formatter;
 
+use crate::data::legality;
+use crate::data::market_state;
 use crate::data::trader::*;
 use crate::data::Universe;
 use crate::search::player_state::PlayerState;
@@ -27,6 +29,14 @@ pub struct UnitTrade<'a> {
 
     pub is_valid: bool,
     pub is_prohibited: bool,
+    // illegal cargo, sold on a black market
+    pub is_smuggled: bool,
+    // the fraction of profit we expect to lose to fines
+    pub risk: f64,
+    // the chance both markets are as listed when we get there, from the station and system states
+    pub reliability: f64,
+    // the expected relative change in the sell price before we arrive
+    pub price_shift: f64,
 
     pub profit_per_ton: u32,
     pub profit_per_ton_per_min: f64,
@@ -78,16 +88,37 @@ impl<'b> UnitTrade<'b> {
         sell_station: &'b Station,
         sell: &'b Listing,
     ) -> UnitTrade<'b> {
-        let normalized_time =
-            TimeEstimate::new_normalized_estimate(state, buy_system, sell_system, sell_station);
-        let adjusted_time =
-            TimeEstimate::new_adjusted_estimate(state, buy_system, sell_system, sell_station);
+        let normalized_time = TimeEstimate::new_normalized_estimate(
+            state,
+            buy_system,
+            buy_station,
+            sell_system,
+            sell_station,
+        );
+        let adjusted_time = TimeEstimate::new_adjusted_estimate(
+            state,
+            buy_system,
+            buy_station,
+            sell_system,
+            sell_station,
+        );
 
         let profit_per_ton = UnitTrade::profit_per_ton(&buy, &sell);
 
         let profit_per_ton_per_min =
             UnitTrade::profit_per_ton_per_min(&buy, &sell, adjusted_time.time_total);
 
+        let is_prohibited =
+            UnitTrade::is_prohibited(&buy.commodity, sell, sell_system, sell_station);
+        let is_smuggled = is_prohibited && sell.is_black_market;
+        let risk = match is_smuggled {
+            true => state.smuggling_risk.unwrap_or(0f64),
+            false => 0f64,
+        };
+
+        let (buy_reliability, _) = market_state::outlook(buy_system, buy_station);
+        let (sell_reliability, price_shift) = market_state::outlook(sell_system, sell_station);
+
         UnitTrade {
             commodity_id: buy.commodity.commodity_id.clone(),
             commodity_name: buy.commodity.commodity_name.clone(),
@@ -104,7 +135,11 @@ impl<'b> UnitTrade<'b> {
             sell_price: sell.sell_price,
 
             is_valid: UnitTrade::is_valid(&buy, &sell),
-            is_prohibited: UnitTrade::is_prohibited(&buy.commodity, &sell_station),
+            is_prohibited: is_prohibited,
+            is_smuggled: is_smuggled,
+            risk: risk,
+            reliability: buy_reliability * sell_reliability,
+            price_shift: price_shift,
 
             profit_per_ton: profit_per_ton,
             profit_per_ton_per_min: profit_per_ton_per_min,
@@ -133,6 +168,19 @@ impl<'b> UnitTrade<'b> {
     pub fn credit_potential(&self) -> u64 {
         self.profit_per_ton as u64 * self.buy.supply as u64
     }
+
+    // the fraction of the listed profit we expect to make, given the market states.
+    // 1.0 when both markets are calm
+    pub fn market_factor(&self) -> f64 {
+        if self.profit_per_ton == 0 {
+            return 0f64;
+        }
+
+        let expected_sell = self.sell_price as f64 * (1f64 + self.price_shift);
+        let expected_profit = (expected_sell - self.buy_price as f64).max(0f64);
+
+        self.reliability * expected_profit / self.profit_per_ton as f64
+    }
 }
 
 impl<'b> UnitTrade<'b> {
@@ -151,10 +199,33 @@ impl<'b> UnitTrade<'b> {
             && buy.commodity.commodity_id == sell.commodity.commodity_id
     }
 
-    pub fn is_prohibited(commodity: &Commodity, sell_station: &Station) -> bool {
-        sell_station
-            .prohibited_commodities
-            .contains(&commodity.commodity_id)
+    // the open market is trusted - if it lists a commodity, the station buys it.
+    // the jurisdiction only matters for what is sold on the black market
+    pub fn is_prohibited(
+        commodity: &Commodity,
+        sell: &Listing,
+        sell_system: &System,
+        sell_station: &Station,
+    ) -> bool {
+        match sell.is_black_market {
+            true => legality::is_illegal(commodity, sell_system, sell_station),
+            false => legality::is_prohibited_by_station(commodity, sell_station),
+        }
+    }
+
+    // illegal cargo can only be sold on a black market, and only if the player is smuggling
+    pub fn is_allowed(
+        state: &PlayerState,
+        commodity: &Commodity,
+        sell: &Listing,
+        sell_system: &System,
+        sell_station: &Station,
+    ) -> bool {
+        if sell.is_black_market {
+            return state.smuggling_risk.is_some();
+        }
+
+        !UnitTrade::is_prohibited(commodity, sell, sell_system, sell_station)
     }
 
     pub fn profit_per_ton_per_min(buy: &Listing, sell: &Listing, distance_in_seconds: f64) -> f64 {
@@ -174,7 +245,7 @@ impl<'b> UnitTrade<'b> {
 
 impl<'a> Scored<f64> for UnitTrade<'a> {
     fn score(&self) -> f64 {
-        self.profit_per_ton_per_min
+        self.profit_per_ton_per_min * (1f64 - self.risk) * self.market_factor()
     }
 }
 
@@ -196,3 +267,69 @@ impl<'b> Debug for UnitTrade<'b> {
         formatter.write_str(&str)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::filter::FilterOptions;
+    use crate::data::source::FixtureSource;
+
+    fn listing(station: &Station, commodity_id: u16) -> &Listing {
+        station
+            .listings
+            .iter()
+            .find(|e| e.commodity.commodity_id == commodity_id)
+            .unwrap()
+    }
+
+    #[test]
+    fn test_listed_goods_are_allowed() {
+        // Siddha Ring is a theocracy, but its market lists narcotics and wine anyway
+        let mut source = FixtureSource::sample();
+        for &(id, name) in &[(3u16, "Narcotics"), (4, "Wine")] {
+            let mut commodity = source.commodities[0].clone();
+            commodity.id = id;
+            commodity.name = name.to_string();
+            source.commodities.push(commodity);
+
+            let mut buy = source.listings[0].clone();
+            buy.id = 10 + id as u32;
+            buy.commodity_id = id;
+            buy.buy_price = 100;
+            source.listings.push(buy);
+
+            let mut sell = source.listings[2].clone();
+            sell.id = 20 + id as u32;
+            sell.commodity_id = id;
+            sell.sell_price = 500;
+            source.listings.push(sell);
+        }
+        for station in source.stations.iter_mut().filter(|e| e.id == 20) {
+            station.government = Some("Theocracy".to_string());
+        }
+
+        let universe = Universe::from_source(&source, &FilterOptions::new(ShipSize::Large));
+        let giger = universe.get_station(10).unwrap();
+        let siddha = universe.get_station(20).unwrap();
+        let chono = universe.get_system(2).unwrap();
+        let state = PlayerState::sample(&universe, 10);
+        assert!(state.smuggling_risk.is_none());
+
+        for id in 3..5 {
+            let buy = listing(giger, id);
+            let sell = listing(siddha, id);
+            assert!(!sell.is_black_market);
+            assert!(legality::is_illegal(&sell.commodity, chono, siddha));
+
+            assert!(UnitTrade::is_allowed(&state, &sell.commodity, sell, chono, siddha));
+            let trade = UnitTrade::new(&universe, &state, buy, sell);
+            assert!(!trade.is_prohibited);
+            assert!(!trade.is_smuggled);
+            assert_eq!(400, trade.profit_per_ton);
+        }
+
+        // explosives are still refused by the station itself
+        let explosives = &giger.listings[1];
+        assert!(UnitTrade::is_prohibited(&explosives.commodity, explosives, chono, siddha));
+    }
+}
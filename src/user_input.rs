@@ -1,5 +1,7 @@
 use std::str::FromStr;
 use std::io::stdin;
+use std::sync::mpsc::{channel, Receiver};
+use std::thread;
 
 pub fn prompt_confirm( message: &str ) -> bool {
 	println!( "{} (y/n): ", message );
@@ -34,6 +36,28 @@ pub fn read_line() -> String {
 	str.trim().to_string()
 }
 
+// reads lines on another thread, so we can wait on stdin and something else at once.
+// once this is called, all input should come through the receiver
+pub fn spawn_line_reader() -> Receiver<String> {
+	let (sender, receiver) = channel();
+	
+	thread::spawn( move || {
+		loop {
+			let mut str = String::new();
+			match stdin().read_line(&mut str) {
+				Ok(0) | Err(_) => break,
+				Ok(_) => {}
+			};
+			
+			if sender.send( str.trim().to_string() ).is_err() {
+				break;
+			}
+		}
+	});
+	
+	receiver
+}
+
 pub fn read_price_update<T: FromStr>( description: &str ) -> T {
 	println!("Enter the updated {}:", description );
 	
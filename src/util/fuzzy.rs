@@ -0,0 +1,118 @@
+use std::cmp::min;
+
+// typo-tolerant name matching, for station and system names typed by the player.
+// lower ranks are better matches:
+//  0 - the same name, ignoring case
+//  1 - the name starts with the query (e.g. 'jameson' for 'Jameson Memorial')
+//  2 - a word in the name starts with the query (e.g. 'memorial')
+//  3+ - a few typos away, ranked by edit distance
+
+const EXACT: usize = 0;
+const PREFIX: usize = 1;
+const WORD_PREFIX: usize = 2;
+const TYPO: usize = 3;
+
+// the rank of the candidate, or None if it is too different to suggest
+pub fn rank(query: &str, candidate: &str) -> Option<usize> {
+    let query = query.trim().to_lowercase();
+    let candidate = candidate.to_lowercase();
+
+    if query.is_empty() {
+        return None;
+    }
+
+    if candidate == query {
+        return Some(EXACT);
+    }
+
+    if candidate.starts_with(&query[..]) {
+        return Some(PREFIX);
+    }
+
+    if candidate
+        .split(|c: char| c.is_whitespace() || c == '-')
+        .any(|word| word.starts_with(&query[..]))
+    {
+        return Some(WORD_PREFIX);
+    }
+
+    // about one typo every four letters
+    let max_distance = 1 + query.chars().count() / 4;
+    let distance = levenshtein(&query, &candidate);
+
+    match distance <= max_distance {
+        true => Some(TYPO + distance),
+        false => None,
+    }
+}
+
+// the best matches for the query, best first.  ties are broken by the shorter name
+pub fn best_matches<'a, T, I>(query: &str, candidates: I, limit: usize) -> Vec<T>
+where
+    I: Iterator<Item = (&'a String, T)>,
+{
+    let mut ranked: Vec<(usize, usize, T)> = candidates
+        .filter_map(|(name, value)| rank(query, name).map(|r| (r, name.len(), value)))
+        .collect();
+
+    ranked.sort_by(|a, b| (a.0, a.1).cmp(&(b.0, b.1)));
+    ranked.into_iter().take(limit).map(|e| e.2).collect()
+}
+
+// the number of single character edits between the strings
+pub fn levenshtein(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..b.len() + 1).collect();
+    let mut current = vec![0; b.len() + 1];
+
+    for (i, ca) in a.chars().enumerate() {
+        current[0] = i + 1;
+
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = match ca == *cb {
+                true => previous[j],
+                false => previous[j] + 1,
+            };
+
+            current[j + 1] = min(substitution, min(previous[j + 1], current[j]) + 1);
+        }
+
+        std::mem::swap(&mut previous, &mut current);
+    }
+
+    previous[b.len()]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_levenshtein() {
+        assert_eq!(0, levenshtein("chono", "chono"));
+        assert_eq!(1, levenshtein("chno", "chono"));
+        assert_eq!(2, levenshtein("siddah ring", "siddha ring"));
+        assert_eq!(5, levenshtein("", "chono"));
+    }
+
+    #[test]
+    fn test_rank() {
+        assert_eq!(Some(EXACT), rank("Siddha Ring", "siddha ring"));
+        assert_eq!(Some(PREFIX), rank("sidd", "Siddha Ring"));
+        assert_eq!(Some(WORD_PREFIX), rank("ring", "Siddha Ring"));
+        assert_eq!(Some(TYPO + 2), rank("siddah ring", "Siddha Ring"));
+        assert_eq!(None, rank("giger hub", "Siddha Ring"));
+        assert_eq!(None, rank("  ", "Siddha Ring"));
+
+        let names = vec![
+            "Small Outpost".to_string(),
+            "Siddha Ring".to_string(),
+            "Giger Hub".to_string(),
+        ];
+        let matches = best_matches("s", names.iter().map(|e| (e, e.clone())), 5);
+        assert_eq!(vec!["Siddha Ring", "Small Outpost"], matches);
+
+        let matches = best_matches("giger hbu", names.iter().map(|e| (e, e.clone())), 5);
+        assert_eq!(vec!["Giger Hub"], matches);
+    }
+}
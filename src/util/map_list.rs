@@ -5,6 +5,7 @@ use std::collections::HashMap;
 
 type ValueList<E> = Vec<E>;
 
+#[derive(Clone)]
 pub struct MapList<K, E> {
     map: HashMap<K, ValueList<E>>,
 }
@@ -1,3 +1,8 @@
+pub mod fuzzy;
 pub mod map_list;
 pub mod num_unit;
-pub mod scored_buf;
\ No newline at end of file
+pub mod scored_buf;
+pub mod stream;
+#[cfg(test)]
+pub mod test_dir;
+pub mod timestamp;
\ No newline at end of file
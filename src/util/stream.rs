@@ -0,0 +1,280 @@
+use std::cell::Cell;
+use std::fmt;
+use std::io::Read;
+use std::mem::replace;
+
+use crossbeam::channel::bounded;
+use crossbeam::channel::Sender;
+use crossbeam::thread::ScopedJoinHandle;
+use csv::StringRecord;
+use num_cpus;
+use serde::de::DeserializeOwned;
+use serde::de::SeqAccess;
+use serde::de::Visitor;
+use serde::Deserializer;
+use serde_json::Value;
+
+// the dumps are hundreds of megabytes, so they are parsed as they are read.
+// the reader thread splits rows into batches, and a worker per cpu deserializes them.
+// rows which don't deserialize are counted and skipped - one bad row shouldn't abort a refresh.
+// batches are numbered as they are sent, so the rows come back in the order of the dump
+
+const BATCH_SIZE: usize = 10000;
+
+// numbers each batch, so the workers' rows can be put back in order
+struct BatchSender<I> {
+    sender: Sender<(usize, Vec<I>)>,
+    sent: Cell<usize>,
+}
+
+impl<I> BatchSender<I> {
+    fn send(&self, batch: Vec<I>) {
+        let index = self.sent.get();
+        self.sent.set(index + 1);
+        self.sender.send((index, batch)).ok();
+    }
+}
+
+pub struct StreamResult<T> {
+    pub rows: Vec<T>,
+    pub skipped: usize,
+}
+
+impl<T> StreamResult<T> {
+    pub fn report(self, name: &str) -> Vec<T> {
+        if self.skipped > 0 {
+            println!(
+                "Loaded {} rows from {}, skipped {} malformed rows",
+                self.rows.len(),
+                name,
+                self.skipped
+            );
+        }
+
+        self.rows
+    }
+}
+
+pub fn read_csv<T, R>(reader: R) -> Result<StreamResult<T>, String>
+where
+    T: DeserializeOwned + Send,
+    R: Read,
+{
+    let mut rdr = csv::Reader::from_reader(reader);
+    let headers = match rdr.headers() {
+        Ok(h) => h.clone(),
+        Err(reason) => {
+            return Err(format!("failed to read csv headers: {}", reason));
+        }
+    };
+
+    parse_parallel(
+        |sender| {
+            let mut batch = Vec::with_capacity(BATCH_SIZE);
+            let mut unreadable = 0;
+
+            for record in rdr.records() {
+                match record {
+                    Ok(r) => batch.push(r),
+                    // a record with the wrong number of fields.  the reader can continue past it
+                    Err(ref e) if !is_io_error(e) => {
+                        unreadable += 1;
+                        continue;
+                    }
+                    Err(reason) => {
+                        return Err(format!("failed to read csv: {}", reason));
+                    }
+                }
+
+                if batch.len() >= BATCH_SIZE {
+                    let full = replace(&mut batch, Vec::with_capacity(BATCH_SIZE));
+                    sender.send(full);
+                }
+            }
+
+            if !batch.is_empty() {
+                sender.send(batch);
+            }
+
+            Ok(unreadable)
+        },
+        |record: StringRecord| record.deserialize(Some(&headers)).ok(),
+    )
+}
+
+// reads a top-level JSON array, one element at a time
+pub fn read_json_array<T, R>(reader: R) -> Result<StreamResult<T>, String>
+where
+    T: DeserializeOwned + Send,
+    R: Read,
+{
+    parse_parallel(
+        |sender| {
+            let mut de = serde_json::Deserializer::from_reader(reader);
+            let visitor = BatchVisitor { sender: sender };
+
+            match (&mut de).deserialize_seq(visitor).and_then(|_| de.end()) {
+                Ok(_) => Ok(0),
+                Err(reason) => Err(format!("failed to read json: {}", reason)),
+            }
+        },
+        |value: Value| serde_json::from_value(value).ok(),
+    )
+}
+
+fn is_io_error(error: &csv::Error) -> bool {
+    match error.kind() {
+        &csv::ErrorKind::Io(_) => true,
+        _ => false,
+    }
+}
+
+fn parse_parallel<I, T, P, F>(produce: P, parse: F) -> Result<StreamResult<T>, String>
+where
+    I: Send,
+    T: Send,
+    P: FnOnce(&BatchSender<I>) -> Result<usize, String>,
+    F: Fn(I) -> Option<T> + Sync,
+{
+    let cpus = num_cpus::get();
+    // bounded, so a slow parse doesn't buffer the whole file in memory
+    let (sender, receiver) = bounded::<(usize, Vec<I>)>(cpus * 2);
+    let parse = &parse;
+
+    crossbeam::scope(|scope| {
+        let mut handles = Vec::new();
+
+        for _ in 0..cpus {
+            let receiver = receiver.clone();
+            let handle: ScopedJoinHandle<(Vec<(usize, Vec<T>)>, usize)> =
+                scope.spawn(move |_scope| {
+                    let mut batches = Vec::new();
+                    let mut skipped = 0;
+
+                    for (index, batch) in receiver.iter() {
+                        let mut rows = Vec::with_capacity(batch.len());
+                        for item in batch {
+                            match parse(item) {
+                                Some(row) => rows.push(row),
+                                None => skipped += 1,
+                            }
+                        }
+
+                        batches.push((index, rows));
+                    }
+
+                    (batches, skipped)
+                });
+
+            handles.push(handle);
+        }
+
+        // the workers stop once the sender is dropped, and the channel drains
+        let sender = BatchSender {
+            sender: sender,
+            sent: Cell::new(0),
+        };
+        let produced = produce(&sender);
+        drop(sender);
+
+        let mut batches = Vec::new();
+        let mut result = StreamResult {
+            rows: Vec::new(),
+            skipped: 0,
+        };
+
+        for handle in handles {
+            let (mut worker_batches, skipped) = handle.join().unwrap();
+            batches.append(&mut worker_batches);
+            result.skipped += skipped;
+        }
+
+        batches.sort_by_key(|e| e.0);
+        for (_, mut rows) in batches {
+            result.rows.append(&mut rows);
+        }
+
+        produced.map(|unreadable| {
+            result.skipped += unreadable;
+            result
+        })
+    })
+    .unwrap()
+}
+
+struct BatchVisitor<'a> {
+    sender: &'a BatchSender<Value>,
+}
+
+impl<'de, 'a> Visitor<'de> for BatchVisitor<'a> {
+    type Value = ();
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("a JSON array")
+    }
+
+    fn visit_seq<A>(self, mut seq: A) -> Result<(), A::Error>
+    where
+        A: SeqAccess<'de>,
+    {
+        let mut batch = Vec::with_capacity(BATCH_SIZE);
+
+        while let Some(value) = seq.next_element::<Value>()? {
+            batch.push(value);
+
+            if batch.len() >= BATCH_SIZE {
+                let full = replace(&mut batch, Vec::with_capacity(BATCH_SIZE));
+                self.sender.send(full);
+            }
+        }
+
+        if !batch.is_empty() {
+            self.sender.send(batch);
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde::Deserialize;
+    use std::io::Cursor;
+
+    #[derive(Deserialize, Debug)]
+    struct Row {
+        id: u32,
+        price: i32,
+    }
+
+    #[test]
+    fn test_read_csv_skips_bad_rows() {
+        let mut text = "id,price\n".to_string();
+        for i in 0..25000 {
+            text.push_str(&format!("{},{}\n", i, i * 2));
+        }
+        text.push_str("not a number,5\n");
+        text.push_str("1,2,3\n");
+
+        let result: StreamResult<Row> = read_csv(Cursor::new(text)).unwrap();
+        assert_eq!(25000, result.rows.len());
+        assert_eq!(2, result.skipped);
+        assert!(result.rows.iter().all(|r| r.price == r.id as i32 * 2));
+
+        // the batches are parsed in parallel, but the rows keep the order of the file
+        assert!(result.rows.iter().enumerate().all(|(i, r)| r.id == i as u32));
+    }
+
+    #[test]
+    fn test_read_json_array_skips_bad_rows() {
+        let text = r#"[{"id":1,"price":2},{"id":"x","price":2},{"id":3,"price":6}]"#;
+
+        let result: StreamResult<Row> = read_json_array(Cursor::new(text)).unwrap();
+        assert_eq!(2, result.rows.len());
+        assert_eq!(1, result.skipped);
+
+        let broken: Result<StreamResult<Row>, String> = read_json_array(Cursor::new("[{\"id\":1,"));
+        assert!(broken.is_err());
+    }
+}
@@ -0,0 +1,10 @@
+use std::env::temp_dir;
+use std::fs::create_dir_all;
+use std::path::PathBuf;
+
+// a scratch directory for tests which touch the filesystem, unique to the test run
+pub fn test_dir(name: &str) -> PathBuf {
+    let dir = temp_dir().join(format!("elite_trader_{}_{}", name, std::process::id()));
+    create_dir_all(&dir).unwrap();
+    dir
+}
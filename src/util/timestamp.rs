@@ -0,0 +1,62 @@
+// parses UTC timestamps into unix seconds.
+// the journal writes 2020-06-14T12:00:00Z, and spansh dumps write 2020-06-14 12:00:00+00
+pub fn parse_utc_timestamp(timestamp: &str) -> Option<i64> {
+    let timestamp = timestamp
+        .trim()
+        .trim_end_matches('Z')
+        .trim_end_matches("+00:00")
+        .trim_end_matches("+00");
+    let mut parts = timestamp.splitn(2, |c| c == 'T' || c == ' ');
+
+    let date: Vec<i64> = parts
+        .next()?
+        .split('-')
+        .map(|e| e.parse().ok())
+        .collect::<Option<Vec<i64>>>()?;
+    let time: Vec<i64> = parts
+        .next()?
+        .split(':')
+        // fractional seconds are truncated
+        .map(|e| e.split('.').next().unwrap_or(e).parse().ok())
+        .collect::<Option<Vec<i64>>>()?;
+
+    if date.len() != 3 || time.len() != 3 {
+        return None;
+    }
+
+    // days since the unix epoch for the proleptic gregorian calendar
+    let (year, month, day) = (date[0], date[1], date[2]);
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = (if year >= 0 { year } else { year - 399 }) / 400;
+    let year_of_era = year - era * 400;
+    let day_of_year = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    let days = era * 146097 + day_of_era - 719468;
+
+    Some(days * 86400 + time[0] * 3600 + time[1] * 60 + time[2])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_utc_timestamp() {
+        assert_eq!(parse_utc_timestamp("1970-01-01T00:00:00Z"), Some(0));
+        assert_eq!(parse_utc_timestamp("2020-06-14T12:00:00Z"), Some(1592136000));
+        assert_eq!(parse_utc_timestamp("2020-02-29T23:59:59Z"), Some(1583020799));
+        assert_eq!(parse_utc_timestamp("not a timestamp"), None);
+    }
+
+    #[test]
+    fn test_parse_spansh_timestamp() {
+        assert_eq!(
+            parse_utc_timestamp("2020-06-14 12:00:00+00"),
+            Some(1592136000)
+        );
+        assert_eq!(
+            parse_utc_timestamp("2020-06-14 12:00:00.250+00"),
+            Some(1592136000)
+        );
+    }
+}